@@ -0,0 +1,576 @@
+use axum::{
+    extract::{Query, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{Html, IntoResponse, Response},
+    routing::{get, post},
+    Form, Router,
+};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    auth::{self, Secret, VerifiedCache},
+    csrf::{self, CsrfToken},
+    mtls,
+    tokens::{self, ApiToken},
+};
+
+type AccountsResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+// 登录会话默认有效期（秒）
+pub const DEFAULT_SESSION_TTL: u64 = 12 * 60 * 60;
+
+const SESSION_COOKIE: &str = "myhs_session";
+
+// 登录失败后的等待时间，减缓暴力猜测密码
+const FAILED_LOGIN_DELAY: Duration = Duration::from_secs(1);
+
+// 内置组：everyone 包括未登录的访客，authenticated 包括所有已登录用户
+const GROUP_EVERYONE: &str = "everyone";
+const GROUP_AUTHENTICATED: &str = "authenticated";
+
+// 放进 Location 头的路径中需要转义的字符
+const PATH_ESCAPE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`');
+
+// 账号文件配置
+#[derive(Debug, Clone)]
+pub struct AccountsConfig {
+    pub file: PathBuf,
+    pub session_ttl: Duration,
+    // 与 Basic 认证共用 --auth-realm
+    pub realm: String,
+}
+
+// 可以按路径授予的权限
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    // 下载文件
+    Read,
+    // 查看目录列表
+    List,
+    // 上传新文件
+    Upload,
+    // 删除或覆盖已有文件
+    Delete,
+}
+
+// 账号文件的内容
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccountsFile {
+    #[serde(default)]
+    users: HashMap<String, UserEntry>,
+    #[serde(default)]
+    rules: Vec<AccessRule>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UserEntry {
+    // bcrypt、SHA 或 APR1 哈希，与 htpasswd 相同
+    password: String,
+    #[serde(default)]
+    groups: Vec<String>,
+    // 管理员在所有路径上拥有全部权限
+    #[serde(default)]
+    admin: bool,
+}
+
+#[derive(Debug)]
+struct Account {
+    secret: Secret,
+    groups: Vec<String>,
+    admin: bool,
+}
+
+// 路径权限规则：授予 users 和 groups 在该路径及其子路径上的 allow 权限
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct AccessRule {
+    #[serde(deserialize_with = "deserialize_rule_path")]
+    path: String,
+    #[serde(default)]
+    users: Vec<String>,
+    #[serde(default)]
+    groups: Vec<String>,
+    allow: Vec<Permission>,
+}
+
+impl AccessRule {
+    fn applies_to(&self, account: Option<(&str, &Account)>) -> bool {
+        if self.groups.iter().any(|group| group == GROUP_EVERYONE) {
+            return true;
+        }
+        let Some((name, account)) = account else {
+            return false;
+        };
+        self.users.iter().any(|user| user == name)
+            || self
+                .groups
+                .iter()
+                .any(|group| group == GROUP_AUTHENTICATED || account.groups.contains(group))
+    }
+}
+
+fn deserialize_rule_path<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    String::deserialize(deserializer).map(|path| mtls::normalize_path(&path))
+}
+
+// 已登录的用户，作为请求扩展传给处理器
+#[derive(Debug, Clone)]
+pub struct Principal {
+    pub user: String,
+}
+
+#[derive(Debug)]
+struct Session {
+    user: String,
+    expires: Instant,
+}
+
+// 账号、路径权限和登录会话
+#[derive(Debug)]
+pub struct Accounts {
+    users: HashMap<String, Account>,
+    rules: Vec<AccessRule>,
+    // 会话只保存在内存中，重启后需要重新登录
+    sessions: Mutex<HashMap<String, Session>>,
+    session_ttl: Duration,
+    // 启用 HTTPS 时会话 Cookie 带上 Secure
+    secure_cookie: bool,
+    // 401 响应中 Basic 认证的 realm
+    realm: String,
+    verified: VerifiedCache,
+}
+
+impl Accounts {
+    pub fn load(config: &AccountsConfig, secure_cookie: bool) -> AccountsResult<Accounts> {
+        let path = &config.file;
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("无法读取账号文件 '{}': {}", path.display(), e))?;
+        let file: AccountsFile = toml::from_str(&text)
+            .map_err(|e| format!("账号文件 '{}' 格式错误: {}", path.display(), e))?;
+
+        let mut users = HashMap::new();
+        for (name, entry) in file.users {
+            let secret = Secret::parse(&entry.password).ok_or_else(|| {
+                format!(
+                    "账号文件 '{}' 中用户 '{}' 的密码不是 bcrypt、SHA 或 APR1 哈希，可以用 `MyHS hash-password` 生成",
+                    path.display(),
+                    name
+                )
+            })?;
+            users.insert(
+                name,
+                Account {
+                    secret,
+                    groups: entry.groups,
+                    admin: entry.admin,
+                },
+            );
+        }
+
+        for rule in &file.rules {
+            if rule.users.is_empty() && rule.groups.is_empty() {
+                return Err(format!("账号文件 '{}' 中路径 {} 的规则没有指定 users 或 groups", path.display(), rule.path).into());
+            }
+            if let Some(user) = rule.users.iter().find(|user| !users.contains_key(*user)) {
+                return Err(format!(
+                    "账号文件 '{}' 中路径 {} 的规则引用了不存在的用户 '{}'",
+                    path.display(),
+                    rule.path,
+                    user
+                )
+                .into());
+            }
+        }
+
+        Ok(Accounts {
+            users,
+            rules: file.rules,
+            sessions: Mutex::new(HashMap::new()),
+            session_ttl: config.session_ttl,
+            secure_cookie,
+            realm: config.realm.clone(),
+            verified: VerifiedCache::default(),
+        })
+    }
+
+    pub fn user_count(&self) -> usize {
+        self.users.len()
+    }
+
+    pub fn rule_count(&self) -> usize {
+        self.rules.len()
+    }
+
+    fn account(&self, principal: Option<&Principal>) -> Option<(&str, &Account)> {
+        let (name, account) = self.users.get_key_value(principal?.user.as_str())?;
+        Some((name.as_str(), account))
+    }
+
+    // 与客户端证书规则相同，由最长的匹配路径决定：该路径上的规则覆盖上级路径的规则，
+    // 同一路径的多条规则授予的权限合并；管理员拥有全部权限，没有规则授予的权限一律拒绝
+    pub fn permits(&self, principal: Option<&Principal>, path: &str, permission: Permission) -> bool {
+        let account = self.account(principal);
+        if account.is_some_and(|(_, account)| account.admin) {
+            return true;
+        }
+        let path = mtls::normalize_path(path);
+        let Some(longest) = self
+            .rules
+            .iter()
+            .filter(|rule| mtls::path_within(&path, &rule.path))
+            .map(|rule| rule.path.len())
+            .max()
+        else {
+            return false;
+        };
+        self.rules.iter().any(|rule| {
+            rule.path.len() == longest
+                && mtls::path_within(&path, &rule.path)
+                && rule.allow.contains(&permission)
+                && rule.applies_to(account)
+        })
+    }
+
+    // 目录列表中是否显示该条目：文件需要读取权限；目录本身或其下某条规则授予了权限即显示
+    pub fn can_see(&self, principal: Option<&Principal>, path: &str, is_dir: bool) -> bool {
+        if !is_dir {
+            return self.permits(principal, path, Permission::Read);
+        }
+        let account = self.account(principal);
+        let path = mtls::normalize_path(path);
+        [Permission::Read, Permission::List, Permission::Upload, Permission::Delete]
+            .into_iter()
+            .any(|permission| self.permits(principal, &path, permission))
+            || self.rules.iter().any(|rule| {
+                rule.path != path && mtls::path_within(&rule.path, &path) && !rule.allow.is_empty() && rule.applies_to(account)
+            })
+    }
+
+    fn verify(&self, user: &str, password: &str) -> bool {
+        self.verified.check(0, user, password, || {
+            self.users.get(user).is_some_and(|account| account.secret.verify(password))
+        })
+    }
+
+    fn create_session(&self, user: &str) -> String {
+        let token = tokens::random_hex(32);
+
+        let now = Instant::now();
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, session| session.expires > now);
+        sessions.insert(
+            token.clone(),
+            Session {
+                user: user.to_string(),
+                expires: now + self.session_ttl,
+            },
+        );
+        token
+    }
+
+    fn session_user(&self, token: &str) -> Option<String> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions
+            .get(token)
+            .filter(|session| session.expires > Instant::now())
+            .map(|session| session.user.clone())
+    }
+
+    fn end_session(&self, token: &str) {
+        self.sessions.lock().unwrap().remove(token);
+    }
+
+    fn session_cookie(&self, token: &str, max_age: u64) -> HeaderValue {
+        let secure = if self.secure_cookie { "; Secure" } else { "" };
+        HeaderValue::from_str(&format!(
+            "{}={}; Path=/; Max-Age={}; HttpOnly; SameSite=Lax{}",
+            SESSION_COOKIE, token, max_age, secure
+        ))
+        .unwrap()
+    }
+}
+
+// 登录页和退出登录的路由
+pub fn router<S>(accounts: Arc<Accounts>) -> Router<S> {
+    Router::new()
+        .route("/login", get(login_page).post(login))
+        .route("/logout", post(logout))
+        .with_state(accounts)
+}
+
+// 识别当前用户：优先使用登录会话，其次是 Authorization: Basic；权限由各处理器按路径检查
+pub async fn identify(State(accounts): State<Option<Arc<Accounts>>>, mut request: Request, next: Next) -> Response {
+    // 已通过 API 令牌认证的请求不再识别用户
+    let Some(accounts) = accounts.filter(|_| request.extensions().get::<ApiToken>().is_none()) else {
+        return next.run(request).await;
+    };
+
+    let mut user = session_token(request.headers()).and_then(|token| accounts.session_user(&token));
+    if user.is_none() {
+        if let Some((name, password)) = auth::basic_credentials(request.headers()) {
+            let checker = accounts.clone();
+            let checked = name.clone();
+            let ok = tokio::task::spawn_blocking(move || checker.verify(&checked, &password))
+                .await
+                .unwrap_or(false);
+            if !ok {
+                tokio::time::sleep(FAILED_LOGIN_DELAY).await;
+                return accounts.unauthorized();
+            }
+            user = Some(name);
+        }
+    }
+
+    if let Some(user) = user {
+        request.extensions_mut().insert(Principal { user });
+    }
+    next.run(request).await
+}
+
+impl Accounts {
+    // 没有权限时的响应：未登录的浏览器跳转到登录页，其他未登录客户端返回 401，已登录用户返回 403
+    pub fn denied(&self, principal: Option<&Principal>, headers: &HeaderMap, path: &str) -> Response {
+        if let Some(principal) = principal {
+            tracing::info!("拒绝用户 {} 访问 {}", principal.user, path);
+            return (StatusCode::FORBIDDEN, "没有访问该路径的权限").into_response();
+        }
+        if accepts_html(headers) {
+            let location = format!("/login?next={}", utf8_percent_encode(path, NON_ALPHANUMERIC));
+            return (StatusCode::SEE_OTHER, [(header::LOCATION, location)]).into_response();
+        }
+        self.unauthorized()
+    }
+
+    fn unauthorized(&self) -> Response {
+        let challenge = format!("Basic realm=\"{}\", charset=\"UTF-8\"", self.realm.replace('"', "'"));
+        (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, challenge)],
+            "需要登录才能访问",
+        )
+            .into_response()
+    }
+}
+
+fn accepts_html(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+// 从 Cookie 头中取出会话令牌
+fn session_token(headers: &HeaderMap) -> Option<String> {
+    cookie_value(headers, SESSION_COOKIE)
+}
+
+pub fn cookie_value(headers: &HeaderMap, cookie: &str) -> Option<String> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == cookie)
+        .map(|(_, value)| value.to_string())
+}
+
+// 登录后跳转的地址只接受本站路径，避免被用作开放跳转
+fn safe_next(next: Option<&str>) -> String {
+    match next {
+        Some(next) if next.starts_with('/') && !next.starts_with("//") && !next.starts_with("/\\") => {
+            utf8_percent_encode(next, PATH_ESCAPE).to_string()
+        }
+        _ => "/".to_string(),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginQuery {
+    next: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LoginForm {
+    username: String,
+    password: String,
+    next: Option<String>,
+    csrf_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LogoutForm {
+    csrf_token: Option<String>,
+}
+
+async fn login_page(Query(query): Query<LoginQuery>, csrf: CsrfToken) -> Response {
+    Html(render_login_page(query.next.as_deref().unwrap_or("/"), "", None, &csrf)).into_response()
+}
+
+async fn login(State(accounts): State<Arc<Accounts>>, csrf: CsrfToken, Form(form): Form<LoginForm>) -> Response {
+    if !csrf.verify(form.csrf_token.as_deref()) {
+        return csrf::rejected();
+    }
+    let checker = accounts.clone();
+    let (user, password) = (form.username.clone(), form.password);
+    let ok = tokio::task::spawn_blocking(move || checker.verify(&user, &password))
+        .await
+        .unwrap_or(false);
+    if !ok {
+        tracing::info!("用户 {} 登录失败", form.username);
+        tokio::time::sleep(FAILED_LOGIN_DELAY).await;
+        let html = render_login_page(
+            form.next.as_deref().unwrap_or("/"),
+            &form.username,
+            Some("用户名或密码错误"),
+            &csrf,
+        );
+        return (StatusCode::UNAUTHORIZED, Html(html)).into_response();
+    }
+
+    let token = accounts.create_session(&form.username);
+    (
+        StatusCode::SEE_OTHER,
+        [
+            (header::SET_COOKIE, accounts.session_cookie(&token, accounts.session_ttl.as_secs())),
+            (header::LOCATION, HeaderValue::from_str(&safe_next(form.next.as_deref())).unwrap()),
+        ],
+    )
+        .into_response()
+}
+
+async fn logout(
+    State(accounts): State<Arc<Accounts>>,
+    csrf: CsrfToken,
+    headers: HeaderMap,
+    form: Option<Form<LogoutForm>>,
+) -> Response {
+    if !csrf.verify(form.as_ref().and_then(|Form(form)| form.csrf_token.as_deref())) {
+        return csrf::rejected();
+    }
+    if let Some(token) = session_token(&headers) {
+        accounts.end_session(&token);
+    }
+    (
+        StatusCode::SEE_OTHER,
+        [
+            (header::SET_COOKIE, accounts.session_cookie("", 0)),
+            (header::LOCATION, HeaderValue::from_static("/login")),
+        ],
+    )
+        .into_response()
+}
+
+fn render_login_page(next: &str, username: &str, error: Option<&str>, csrf: &CsrfToken) -> String {
+    let error = error
+        .map(|error| format!(r#"<div class="error">{}</div>"#, html_escape(error)))
+        .unwrap_or_default();
+    format!(r#"
+<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>登录</title>
+    <style>
+        body {{
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
+            margin: 0;
+            padding: 20px;
+            background-color: #f5f5f5;
+        }}
+        .container {{
+            max-width: 360px;
+            margin: 60px auto;
+            background: white;
+            border-radius: 8px;
+            box-shadow: 0 2px 10px rgba(0,0,0,0.1);
+            overflow: hidden;
+        }}
+        .header {{
+            background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+            color: white;
+            padding: 20px;
+            text-align: center;
+        }}
+        .header h1 {{
+            margin: 0;
+            font-size: 1.5rem;
+        }}
+        form {{
+            padding: 20px;
+            display: flex;
+            flex-direction: column;
+            gap: 12px;
+        }}
+        input {{
+            padding: 10px;
+            border: 1px solid #dee2e6;
+            border-radius: 4px;
+            font-size: 1rem;
+        }}
+        button {{
+            padding: 10px;
+            background-color: #007bff;
+            color: white;
+            border: none;
+            border-radius: 4px;
+            font-size: 1rem;
+            cursor: pointer;
+        }}
+        button:hover {{
+            background-color: #0069d9;
+        }}
+        .error {{
+            color: #dc3545;
+        }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <div class="header">
+            <h1>🔑 登录</h1>
+        </div>
+        <form action="/login" method="post">
+            {}
+            {}
+            <input type="hidden" name="next" value="{}">
+            <input type="text" name="username" placeholder="用户名" value="{}" autocomplete="username" required autofocus>
+            <input type="password" name="password" placeholder="密码" autocomplete="current-password" required>
+            <button type="submit">登录</button>
+        </form>
+    </div>
+</body>
+</html>
+    "#, error, csrf.form_field(), html_escape(next), html_escape(username))
+}
+
+pub fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
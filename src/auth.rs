@@ -0,0 +1,395 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use md5::{Digest as _, Md5};
+use sha1::Sha1;
+use sha2::Sha256;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path as StdPath, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use crate::tokens::ApiToken;
+
+type AuthResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+pub const DEFAULT_REALM: &str = "MyHS";
+
+// 缓存的已验证凭据数量上限；bcrypt 校验很慢，而浏览器每个请求都会带上凭据
+const VERIFIED_CACHE_LIMIT: usize = 1024;
+
+// 命令行或配置文件中的 用户名:密码
+#[derive(Debug, Clone, serde::Deserialize)]
+#[serde(try_from = "String")]
+pub struct UserSpec {
+    pub user: String,
+    pub password: String,
+}
+
+impl FromStr for UserSpec {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.split_once(':') {
+            Some((user, password)) if !user.is_empty() => Ok(UserSpec {
+                user: user.to_string(),
+                password: password.to_string(),
+            }),
+            _ => Err("账号格式应为 用户名:密码".to_string()),
+        }
+    }
+}
+
+impl TryFrom<String> for UserSpec {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+// Basic 认证配置
+#[derive(Debug, Clone, Default)]
+pub struct AuthConfig {
+    pub realm: String,
+    // 浏览和下载需要的账号；上传账号同样可以浏览
+    pub users: Vec<UserSpec>,
+    pub htpasswd: Option<PathBuf>,
+    // 上传需要的账号；未配置时上传沿用浏览账号
+    pub upload_users: Vec<UserSpec>,
+    pub upload_htpasswd: Option<PathBuf>,
+}
+
+impl AuthConfig {
+    pub fn is_enabled(&self) -> bool {
+        !self.users.is_empty()
+            || self.htpasswd.is_some()
+            || !self.upload_users.is_empty()
+            || self.upload_htpasswd.is_some()
+    }
+}
+
+// 密码的保存形式
+#[derive(Debug)]
+pub enum Secret {
+    // 命令行或配置文件中直接给出的密码
+    Plain(String),
+    // $2y$ / $2a$ / $2b$
+    Bcrypt(String),
+    // {SHA}base64(sha1)
+    Sha1(Vec<u8>),
+    // $apr1$salt$hash
+    Apr1 { salt: String, hash: String },
+}
+
+impl Secret {
+    // 识别 htpasswd 中的哈希格式；明文密码不会被当成哈希
+    pub fn parse(hash: &str) -> Option<Secret> {
+        if hash.starts_with("$2y$") || hash.starts_with("$2a$") || hash.starts_with("$2b$") {
+            Some(Secret::Bcrypt(hash.to_string()))
+        } else if let Some(digest) = hash.strip_prefix("{SHA}") {
+            BASE64.decode(digest).ok().map(Secret::Sha1)
+        } else if let Some(rest) = hash.strip_prefix("$apr1$") {
+            let (salt, hash) = rest.split_once('$')?;
+            Some(Secret::Apr1 {
+                salt: salt.to_string(),
+                hash: hash.to_string(),
+            })
+        } else {
+            None
+        }
+    }
+
+    pub fn verify(&self, password: &str) -> bool {
+        match self {
+            Secret::Plain(expected) => constant_time_eq(expected.as_bytes(), password.as_bytes()),
+            Secret::Bcrypt(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            Secret::Sha1(digest) => constant_time_eq(digest, &Sha1::digest(password.as_bytes())),
+            Secret::Apr1 { salt, hash } => constant_time_eq(hash.as_bytes(), apr1(password, salt).as_bytes()),
+        }
+    }
+}
+
+// 一组账号
+#[derive(Debug, Default)]
+struct UserList {
+    users: HashMap<String, Secret>,
+}
+
+impl UserList {
+    fn load(users: &[UserSpec], htpasswd: Option<&StdPath>) -> AuthResult<Option<UserList>> {
+        if users.is_empty() && htpasswd.is_none() {
+            return Ok(None);
+        }
+        let mut list = UserList::default();
+        if let Some(path) = htpasswd {
+            list.load_htpasswd(path)?;
+        }
+        for spec in users {
+            list.users.insert(spec.user.clone(), Secret::Plain(spec.password.clone()));
+        }
+        Ok(Some(list))
+    }
+
+    // 读取 Apache htpasswd 文件，支持 bcrypt、SHA 和 APR1 三种格式
+    fn load_htpasswd(&mut self, path: &StdPath) -> AuthResult<()> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("无法读取 htpasswd 文件 '{}': {}", path.display(), e))?;
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let secret = line
+                .split_once(':')
+                .and_then(|(user, hash)| Some((user, Secret::parse(hash)?)))
+                .filter(|(user, _)| !user.is_empty());
+            match secret {
+                Some((user, secret)) => {
+                    self.users.insert(user.to_string(), secret);
+                }
+                None => {
+                    return Err(format!(
+                        "htpasswd 文件 '{}' 第 {} 行无法识别，只支持 bcrypt（htpasswd -B）、SHA（-s）和 APR1（-m）格式",
+                        path.display(),
+                        number + 1
+                    )
+                    .into())
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn verify(&self, user: &str, password: &str) -> bool {
+        self.users.get(user).is_some_and(|secret| secret.verify(password))
+    }
+}
+
+// 已验证过的凭据摘要，避免每个请求都重新计算 bcrypt
+#[derive(Debug, Default)]
+pub struct VerifiedCache {
+    keys: Mutex<HashSet<[u8; 32]>>,
+}
+
+impl VerifiedCache {
+    // scope 区分同一账号在不同用途下的验证结果；verify 只在缓存未命中时调用
+    pub fn check(&self, scope: u8, user: &str, password: &str, verify: impl FnOnce() -> bool) -> bool {
+        let key: [u8; 32] = Sha256::new()
+            .chain_update([scope])
+            .chain_update(user.as_bytes())
+            .chain_update([0])
+            .chain_update(password.as_bytes())
+            .finalize()
+            .into();
+        if self.keys.lock().unwrap().contains(&key) {
+            return true;
+        }
+
+        let ok = verify();
+        if ok {
+            let mut keys = self.keys.lock().unwrap();
+            if keys.len() >= VERIFIED_CACHE_LIMIT {
+                keys.clear();
+            }
+            keys.insert(key);
+        }
+        ok
+    }
+}
+
+// HTTP Basic 认证
+pub struct BasicAuth {
+    realm: String,
+    read: Option<UserList>,
+    upload: Option<UserList>,
+    verified: VerifiedCache,
+}
+
+impl BasicAuth {
+    pub fn load(config: &AuthConfig) -> AuthResult<BasicAuth> {
+        Ok(BasicAuth {
+            realm: config.realm.clone(),
+            read: UserList::load(&config.users, config.htpasswd.as_deref())?,
+            upload: UserList::load(&config.upload_users, config.upload_htpasswd.as_deref())?,
+            verified: VerifiedCache::default(),
+        })
+    }
+
+    // 上传使用单独账号时给它一个不同的 realm，浏览器会分别询问两套账号；
+    // 浏览器按 Latin-1 显示 realm，后缀只用 ASCII
+    fn realm(&self, upload: bool) -> String {
+        if upload && self.upload.is_some() && self.read.is_some() {
+            format!("{} (upload)", self.realm)
+        } else {
+            self.realm.clone()
+        }
+    }
+
+    // 浏览时接受浏览账号和上传账号；上传时只接受上传账号（未配置时为浏览账号）
+    fn lists(&self, upload: bool) -> Vec<&UserList> {
+        if upload {
+            self.upload.iter().chain(self.read.iter()).take(1).collect()
+        } else if self.read.is_some() {
+            self.read.iter().chain(self.upload.iter()).collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn verify(&self, upload: bool, user: &str, password: &str) -> bool {
+        self.verified.check(upload as u8, user, password, || {
+            self.lists(upload).iter().any(|list| list.verify(user, password))
+        })
+    }
+}
+
+// 检查 Authorization 头的中间件；POST /upload 和 DELETE 使用上传账号，其余请求使用浏览账号
+pub async fn require_basic(State(auth): State<Arc<BasicAuth>>, request: Request, next: Next) -> Response {
+    let upload = (request.method() == Method::POST && request.uri().path() == "/upload")
+        || request.method() == Method::DELETE;
+    // 已通过 API 令牌认证的请求由令牌的范围决定权限
+    if auth.lists(upload).is_empty() || request.extensions().get::<ApiToken>().is_some() {
+        return next.run(request).await;
+    }
+
+    if let Some((user, password)) = basic_credentials(request.headers()) {
+        let checker = auth.clone();
+        let ok = tokio::task::spawn_blocking(move || checker.verify(upload, &user, &password))
+            .await
+            .unwrap_or(false);
+        if ok {
+            return next.run(request).await;
+        }
+    }
+
+    let challenge = format!("Basic realm=\"{}\", charset=\"UTF-8\"", auth.realm(upload).replace('"', "'"));
+    let mut response = (StatusCode::UNAUTHORIZED, "需要登录才能访问").into_response();
+    if let Ok(value) = HeaderValue::from_str(&challenge) {
+        response.headers_mut().insert(header::WWW_AUTHENTICATE, value);
+    }
+    response
+}
+
+// 解析 Authorization: Basic base64(用户名:密码)
+pub fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, encoded) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(BASE64.decode(encoded.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+// Apache 的 APR1（基于 MD5 的 crypt），返回 $apr1$salt$ 之后的部分
+fn apr1(password: &str, salt: &str) -> String {
+    const MAGIC: &[u8] = b"$apr1$";
+    let password = password.as_bytes();
+    let salt = &salt.as_bytes()[..salt.len().min(8)];
+
+    let alternate = Md5::new()
+        .chain_update(password)
+        .chain_update(salt)
+        .chain_update(password)
+        .finalize();
+
+    let mut context = Md5::new().chain_update(password).chain_update(MAGIC).chain_update(salt);
+    for chunk in (0..password.len()).step_by(16) {
+        context.update(&alternate[..(password.len() - chunk).min(16)]);
+    }
+    let mut i = password.len();
+    while i > 0 {
+        if i & 1 == 1 {
+            context.update([0u8]);
+        } else {
+            context.update(&password[..1]);
+        }
+        i >>= 1;
+    }
+    let mut digest = context.finalize();
+
+    for round in 0..1000 {
+        let mut context = Md5::new();
+        if round & 1 == 1 {
+            context.update(password);
+        } else {
+            context.update(digest);
+        }
+        if round % 3 != 0 {
+            context.update(salt);
+        }
+        if round % 7 != 0 {
+            context.update(password);
+        }
+        if round & 1 == 1 {
+            context.update(digest);
+        } else {
+            context.update(password);
+        }
+        digest = context.finalize();
+    }
+
+    const ITOA64: &[u8] = b"./0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+    let mut encoded = String::with_capacity(22);
+    let mut push = |value: u32, count: usize| {
+        let mut value = value;
+        for _ in 0..count {
+            encoded.push(ITOA64[(value & 0x3f) as usize] as char);
+            value >>= 6;
+        }
+    };
+    for (a, b, c) in [(0, 6, 12), (1, 7, 13), (2, 8, 14), (3, 9, 15), (4, 10, 5)] {
+        push(((digest[a] as u32) << 16) | ((digest[b] as u32) << 8) | digest[c] as u32, 4);
+    }
+    push(digest[11] as u32, 2);
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 以下哈希由 openssl passwd -apr1 和 sha1 + base64 生成
+    #[test]
+    fn verifies_apr1_hashes() {
+        let cases = [
+            ("$apr1$saltsalt$yAAkm4libquA.ZWLHbSBq/", "password"),
+            ("$apr1$a/b.c$OdzXLsAg1UxcYczpG86AU.", "pässwörd with spaces and a long tail"),
+            ("$apr1$abcdefgh$82JubPF2dQkt1tVfvLnXS.", "x"),
+        ];
+        for (hash, password) in cases {
+            let secret = Secret::parse(hash).unwrap();
+            assert!(secret.verify(password), "{}", hash);
+            assert!(!secret.verify(&format!("{}!", password)), "{}", hash);
+        }
+        assert_eq!(apr1("password", "saltsalt"), "yAAkm4libquA.ZWLHbSBq/");
+    }
+
+    #[test]
+    fn verifies_sha_hashes() {
+        let secret = Secret::parse("{SHA}W6ph5Mm5Pz8GgiULbPgzG37mj9g=").unwrap();
+        assert!(secret.verify("password"));
+        assert!(!secret.verify("Password"));
+        assert!(!secret.verify(""));
+    }
+
+    #[test]
+    fn rejects_unknown_hash_formats() {
+        assert!(Secret::parse("password").is_none());
+        assert!(Secret::parse("$1$salt$hash").is_none());
+        assert!(Secret::parse("$apr1$nohash").is_none());
+        assert!(Secret::parse("{SHA}not base64!").is_none());
+    }
+}
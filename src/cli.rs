@@ -0,0 +1,424 @@
+use clap::{CommandFactory, Parser, Subcommand};
+use clap_complete::Shell;
+use std::{
+    io::IsTerminal,
+    path::{Path as StdPath, PathBuf},
+};
+
+use crate::{
+    auth::UserSpec,
+    listener::BindSpec,
+    mtls::ClientRule,
+    shares::{self, ShareConfig},
+    tokens::{self, Scope},
+    upload::ConflictPolicy,
+};
+
+pub const DEFAULT_PORT: u16 = 2333;
+
+// 命令行参数；兼容旧的 `MyHS [目录] [端口]` 位置参数写法
+#[derive(Debug, Parser)]
+#[command(
+    name = "MyHS",
+    version,
+    about = "Python风格的HTTP文件服务器",
+    after_help = "示例:\n  MyHS\n  MyHS /srv/files 8080\n  MyHS --directory /srv/files --port 8080 --bind 127.0.0.1 --read-only\n  MyHS --bind 127.0.0.1 --bind [::1] --bind 10.8.0.1:9000\n  MyHS --tls-cert fullchain.pem --tls-key privkey.pem --port 8443\n  MyHS --tls-self-signed\n  MyHS --tls-cert fullchain.pem --tls-key privkey.pem --port 443 --redirect-http 0.0.0.0:80 --hsts-max-age 31536000\n  MyHS --tls-self-signed --accounts users.toml\n  MyHS hash-password\n  MyHS --token-file tokens.toml token create --name ci --scope upload --path /releases --expires 90d\n  MyHS --share-file shares.toml share create docs/report.pdf --max-downloads 3\n  MyHS --share-file shares.toml share upload incoming/vendor --max-files 20 --max-size 2G"
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// 配置文件路径，默认读取当前目录下的 myhs.toml（如果存在）
+    #[arg(short, long, value_name = "文件")]
+    pub config: Option<PathBuf>,
+
+    /// 服务目录（旧写法，等同于 --directory）
+    #[arg(value_name = "目录", conflicts_with = "directory")]
+    pub legacy_directory: Option<PathBuf>,
+
+    /// 监听端口（旧写法，等同于 --port）
+    #[arg(value_name = "端口", value_parser = parse_port, conflicts_with = "port")]
+    pub legacy_port: Option<u16>,
+
+    /// 服务目录，默认为当前目录
+    #[arg(short, long, value_name = "目录")]
+    pub directory: Option<PathBuf>,
+
+    /// 监听地址，可多次指定；支持 IPv4、IPv6（[::] 同时接受 IPv4）和 地址:端口 形式，默认 0.0.0.0
+    #[arg(short, long, value_name = "地址")]
+    pub bind: Vec<BindSpec>,
+
+    /// 监听端口，默认 2333；0 表示由系统分配
+    #[arg(short, long, value_name = "端口", value_parser = parse_port)]
+    pub port: Option<u16>,
+
+    /// 端口被占用时自动尝试后续端口，仍不可用时由系统分配
+    #[arg(long)]
+    pub port_auto: bool,
+
+    /// 启动后将实际监听的端口和地址以 JSON 写入该文件
+    #[arg(long, value_name = "文件")]
+    pub port_file: Option<PathBuf>,
+
+    /// 以一行 JSON 向标准输出打印启动信息，代替默认的欢迎信息
+    #[arg(long)]
+    pub json: bool,
+
+    /// 启用 HTTPS：PEM 格式的证书文件，可包含完整证书链
+    #[arg(long, value_name = "文件", requires = "tls_key", conflicts_with = "tls_self_signed")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// 启用 HTTPS：PEM 格式的私钥文件（PKCS#8、PKCS#1 或 SEC1）
+    #[arg(long, value_name = "文件", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// 启用 HTTPS：启动时为本机地址生成自签名证书并打印指纹
+    #[arg(long)]
+    pub tls_self_signed: bool,
+
+    /// 要求客户端出示由该 CA（PEM，可包含多个证书）签发的证书
+    #[arg(long, value_name = "文件")]
+    pub tls_client_ca: Option<PathBuf>,
+
+    /// 按证书身份限制路径，可多次指定，例如 /releases=CN:ci-runner,DNS:build01.internal
+    #[arg(long, value_name = "规则")]
+    pub tls_client_allow: Vec<ClientRule>,
+
+    /// 额外监听的 HTTP 地址，只把请求跳转到 HTTPS，可多次指定；未写端口时使用 80
+    #[arg(long, value_name = "地址")]
+    pub redirect_http: Vec<BindSpec>,
+
+    /// 在 HTTPS 响应中加入 Strict-Transport-Security，有效期为该秒数
+    #[arg(long, value_name = "秒")]
+    pub hsts_max_age: Option<u64>,
+
+    /// HSTS 同时作用于所有子域名
+    #[arg(long, requires = "hsts_max_age")]
+    pub hsts_include_subdomains: bool,
+
+    /// HSTS 带上 preload 标记
+    #[arg(long, requires = "hsts_max_age")]
+    pub hsts_preload: bool,
+
+    /// 在同一端口上通过 UDP 同时提供 HTTP/3（QUIC），需要启用 HTTPS，且编译时启用 http3 功能
+    #[arg(long)]
+    pub http3: bool,
+
+    /// 收到 Ctrl+C / SIGTERM 后等待进行中请求完成的最长秒数，默认 30
+    #[arg(long, value_name = "秒")]
+    pub shutdown_timeout: Option<u64>,
+
+    /// 只读模式：禁用文件上传
+    #[arg(long)]
+    pub read_only: bool,
+
+    /// 单个上传请求的大小上限，例如 500M、10G，默认 4G
+    #[arg(long, value_name = "大小", value_parser = parse_size)]
+    pub max_upload_size: Option<u64>,
+
+    /// 单个上传文件的大小上限，默认只受请求大小上限限制
+    #[arg(long, value_name = "大小", value_parser = parse_size)]
+    pub max_file_size: Option<u64>,
+
+    /// 上传的文件写入磁盘后先 fsync 再改为最终文件名，断电时也不会留下不完整的文件
+    #[arg(long)]
+    pub fsync_uploads: bool,
+
+    /// 上传的文件与已有文件同名时的默认处理方式：覆盖、改名为 name (1).ext、跳过或以 409 报错，默认 overwrite
+    #[arg(long, value_name = "方式", value_enum)]
+    pub on_conflict: Option<ConflictPolicy>,
+
+    /// 浏览和下载需要的账号（用户名:密码），可多次指定
+    #[arg(long, value_name = "用户名:密码")]
+    pub auth: Vec<UserSpec>,
+
+    /// 浏览和下载账号的 htpasswd 文件（bcrypt、SHA 或 APR1）
+    #[arg(long, value_name = "文件")]
+    pub htpasswd: Option<PathBuf>,
+
+    /// 上传和删除需要的账号（用户名:密码），可多次指定；未设置时上传沿用浏览账号
+    #[arg(long, value_name = "用户名:密码")]
+    pub upload_auth: Vec<UserSpec>,
+
+    /// 上传账号的 htpasswd 文件
+    #[arg(long, value_name = "文件")]
+    pub upload_htpasswd: Option<PathBuf>,
+
+    /// Basic 认证的 realm，显示在浏览器的登录框中
+    #[arg(long, value_name = "名称")]
+    pub auth_realm: Option<String>,
+
+    /// 多用户账号文件（TOML），包含密码哈希、用户组和按路径授予的权限，启用登录页
+    #[arg(long, value_name = "文件")]
+    pub accounts: Option<PathBuf>,
+
+    /// 登录会话的有效期（秒），默认 43200（12 小时）
+    #[arg(long, value_name = "秒")]
+    pub session_ttl: Option<u64>,
+
+    /// API 令牌文件，由 `MyHS token` 子命令维护；请求可以用 Authorization: Bearer 携带令牌
+    #[arg(long, value_name = "文件", global = true)]
+    pub token_file: Option<PathBuf>,
+
+    /// 分享链接文件，由 `MyHS share` 子命令维护，链接形如 /s/<令牌>
+    #[arg(long, value_name = "文件", global = true)]
+    pub share_file: Option<PathBuf>,
+
+    /// 关闭动态压缩（预压缩文件仍会使用）
+    #[arg(long)]
+    pub no_compression: bool,
+
+    /// 动态压缩的最小响应大小（字节）
+    #[arg(long, value_name = "字节")]
+    pub compression_min_size: Option<u64>,
+
+    /// 允许跨域访问的来源，可多次指定；这些来源也可以提交上传等修改类请求
+    #[arg(long, value_name = "来源")]
+    pub cors_origin: Vec<String>,
+
+    /// 允许任何来源跨域读取（默认不允许跨域）；不影响上传等修改类请求的来源检查
+    #[arg(long)]
+    pub cors_permissive: bool,
+
+    /// 日志过滤规则，例如 myhs=debug,tower_http=debug
+    #[arg(long, value_name = "规则")]
+    pub log: Option<String>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// 输出 shell 自动补全脚本
+    Completions {
+        /// 目标 shell
+        shell: Shell,
+    },
+    /// 从标准输入读取密码，输出可写入账号文件或 htpasswd 的 bcrypt 哈希
+    HashPassword,
+    /// 管理 API 令牌（令牌文件由 --token-file、MYHS_TOKEN_FILE 或配置文件 [tokens] file 指定）
+    Token {
+        #[command(subcommand)]
+        action: TokenCommand,
+    },
+    /// 管理分享链接（分享链接文件由 --share-file、MYHS_SHARE_FILE 或配置文件 [share] file 指定）
+    Share {
+        #[command(subcommand)]
+        action: ShareCommand,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ShareCommand {
+    /// 为服务目录中的一个文件或目录创建分享链接
+    Create {
+        /// 相对服务目录的路径，例如 docs/report.pdf
+        #[arg(value_name = "路径")]
+        path: String,
+        /// 过期时间：7d、12h 这样的时长或 YYYY-MM-DD 日期，默认 7 天
+        #[arg(long, value_name = "期限", value_parser = tokens::parse_expiry)]
+        expires: Option<u64>,
+        /// 最多允许下载的次数
+        #[arg(long, value_name = "次数")]
+        max_downloads: Option<u64>,
+        /// 打印完整链接时使用的地址，例如 https://files.example.com
+        #[arg(long, value_name = "地址", default_value = "")]
+        base_url: String,
+    },
+    /// 创建上传链接：持有链接的人可以向一个目录上传文件，但看不到其中已有的文件
+    Upload {
+        /// 接收文件的目录，相对服务目录，例如 incoming/vendor
+        #[arg(value_name = "目录")]
+        path: String,
+        /// 过期时间：7d、12h 这样的时长或 YYYY-MM-DD 日期，默认 7 天
+        #[arg(long, value_name = "期限", value_parser = tokens::parse_expiry)]
+        expires: Option<u64>,
+        /// 最多允许上传的文件数
+        #[arg(long, value_name = "个数")]
+        max_files: Option<u64>,
+        /// 所有文件的总大小上限，例如 500M、2G
+        #[arg(long, value_name = "大小", value_parser = parse_size)]
+        max_size: Option<u64>,
+        /// 打印完整链接时使用的地址
+        #[arg(long, value_name = "地址", default_value = "")]
+        base_url: String,
+    },
+    /// 列出所有分享链接和上传链接
+    List {
+        /// 打印完整链接时使用的地址
+        #[arg(long, value_name = "地址", default_value = "")]
+        base_url: String,
+    },
+    /// 吊销分享链接或上传链接，正在运行的服务器立即生效
+    Revoke {
+        /// 分享链接 ID
+        #[arg(value_name = "ID")]
+        id: String,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum TokenCommand {
+    /// 创建令牌，完整令牌只在创建时显示一次
+    Create {
+        /// 令牌名称，例如 ci
+        #[arg(long, value_name = "名称")]
+        name: String,
+        /// 授予的范围，可多次指定
+        #[arg(long = "scope", value_name = "范围", required = true)]
+        scopes: Vec<Scope>,
+        /// 允许使用的路径前缀，可多次指定，默认 /
+        #[arg(long = "path", value_name = "路径")]
+        paths: Vec<String>,
+        /// 过期时间：30d、12h 这样的时长或 YYYY-MM-DD 日期，默认永不过期
+        #[arg(long, value_name = "期限", value_parser = tokens::parse_expiry)]
+        expires: Option<u64>,
+    },
+    /// 列出所有令牌
+    List,
+    /// 吊销令牌，正在运行的服务器立即生效
+    Revoke {
+        /// 令牌 ID 或名称
+        #[arg(value_name = "ID或名称")]
+        id: String,
+    },
+}
+
+// 向标准输出写入补全脚本
+pub fn print_completions(shell: Shell) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    clap_complete::generate(shell, &mut command, name, &mut std::io::stdout());
+}
+
+// 从标准输入读取一行密码并输出 bcrypt 哈希
+pub fn print_password_hash() -> Result<(), Box<dyn std::error::Error>> {
+    if std::io::stdin().is_terminal() {
+        eprint!("密码: ");
+    }
+    let mut password = String::new();
+    std::io::stdin().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        return Err("密码不能为空".into());
+    }
+    println!("{}", bcrypt::hash(password, bcrypt::DEFAULT_COST)?);
+    Ok(())
+}
+
+// 执行 token 子命令
+pub fn run_token_command(action: &TokenCommand, token_file: Option<&StdPath>) -> Result<(), Box<dyn std::error::Error>> {
+    let path = token_file.ok_or("未指定令牌文件（--token-file / MYHS_TOKEN_FILE / 配置文件 [tokens] file）")?;
+    match action {
+        TokenCommand::Create {
+            name,
+            scopes,
+            paths,
+            expires,
+        } => {
+            let token = tokens::create(
+                path,
+                tokens::NewToken {
+                    name: name.clone(),
+                    scopes: scopes.clone(),
+                    paths: paths.clone(),
+                    expires: *expires,
+                },
+            )?;
+            eprintln!("已创建令牌 '{}'，请立即保存，之后无法再次查看:", name);
+            println!("{}", token);
+        }
+        TokenCommand::List => tokens::print_list(path)?,
+        TokenCommand::Revoke { id } => {
+            let name = tokens::revoke(path, id)?;
+            println!("已吊销令牌 '{}'", name);
+        }
+    }
+    Ok(())
+}
+
+// 执行 share 子命令；base_dir 用于确认要分享的路径存在
+pub fn run_share_command(
+    action: &ShareCommand,
+    share: Option<&ShareConfig>,
+    base_dir: &StdPath,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let share = share.ok_or("未指定分享链接文件（--share-file / MYHS_SHARE_FILE / 配置文件 [share] file）")?;
+    match action {
+        ShareCommand::Create {
+            path,
+            expires,
+            max_downloads,
+            base_url,
+        } => {
+            let expires = match expires {
+                Some(expires) => *expires,
+                None => tokens::parse_expiry(shares::DEFAULT_SHARE_EXPIRY)?,
+            };
+            let token = shares::create(
+                share,
+                base_dir,
+                shares::NewShare {
+                    path: path.clone(),
+                    expires: Some(expires),
+                    max_downloads: *max_downloads,
+                },
+            )?;
+            println!("{}/s/{}", base_url.trim_end_matches('/'), token);
+        }
+        ShareCommand::Upload {
+            path,
+            expires,
+            max_files,
+            max_size,
+            base_url,
+        } => {
+            let expires = match expires {
+                Some(expires) => *expires,
+                None => tokens::parse_expiry(shares::DEFAULT_SHARE_EXPIRY)?,
+            };
+            let token = shares::create_upload(
+                share,
+                base_dir,
+                shares::NewUploadLink {
+                    path: path.clone(),
+                    expires: Some(expires),
+                    max_files: *max_files,
+                    max_bytes: *max_size,
+                },
+            )?;
+            println!("{}/u/{}", base_url.trim_end_matches('/'), token);
+        }
+        ShareCommand::List { base_url } => shares::print_list(share, base_url.trim_end_matches('/'))?,
+        ShareCommand::Revoke { id } => {
+            let path = shares::revoke(share, id)?;
+            println!("已吊销 {} 的分享链接", path);
+        }
+    }
+    Ok(())
+}
+
+fn parse_port(value: &str) -> Result<u16, String> {
+    value
+        .parse::<u16>()
+        .map_err(|_| format!("'{}' 不是有效的端口号（0-65535，0 表示由系统分配）", value))
+}
+
+// 字节数，可以带 K、M、G、T 后缀（按 1024 进位），例如 512K、100M、2G
+pub fn parse_size(value: &str) -> Result<u64, String> {
+    let invalid = || format!("'{}' 不是有效的大小，应为字节数或 512K、100M、2G 这样的值", value);
+    let value = value.trim();
+    let number = value.trim_end_matches(|c: char| c.is_ascii_alphabetic());
+    let multiplier: u64 = match value[number.len()..].to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(invalid()),
+    };
+    number
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(multiplier))
+        .ok_or_else(invalid)
+}
@@ -0,0 +1,227 @@
+use async_compression::{
+    tokio::bufread::{BrotliEncoder, GzipEncoder, ZstdEncoder},
+    Level,
+};
+use axum::{
+    body::Body,
+    http::{header, HeaderMap, HeaderValue},
+};
+use std::{
+    io::Cursor,
+    path::{Path as StdPath, PathBuf},
+};
+use tokio::{fs::File, io::BufReader};
+use tokio_util::io::ReaderStream;
+
+use crate::file_stream::CHUNK_SIZE;
+
+// 小于该大小的响应压缩收益很低，默认不压缩
+pub const DEFAULT_MIN_SIZE: u64 = 1024;
+
+// 动态压缩配置
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionConfig {
+    pub enabled: bool,
+    pub min_size: u64,
+}
+
+impl CompressionConfig {
+    // 判断某个响应是否值得压缩；返回协商出的编码
+    pub fn negotiate(&self, req_headers: &HeaderMap, content_type: &str, size: u64) -> Option<Encoding> {
+        if !self.enabled || size < self.min_size || !is_compressible(content_type) {
+            return None;
+        }
+        Encoding::negotiate(req_headers)
+    }
+
+    // 开启压缩时，可压缩的响应即使本次未压缩也要声明 Vary
+    pub fn varies(&self, content_type: &str) -> bool {
+        self.enabled && is_compressible(content_type)
+    }
+}
+
+// 响应可能因 Accept-Encoding 不同而变化时声明 Vary，避免共享缓存混用不同编码
+pub fn add_vary(headers: &mut HeaderMap) {
+    headers.append(header::VARY, HeaderValue::from_static("accept-encoding"));
+}
+
+// 支持的内容编码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Brotli,
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    // 相同权重时按此顺序优先选择
+    const PREFERENCE: [Encoding; 3] = [Encoding::Brotli, Encoding::Zstd, Encoding::Gzip];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    // 预压缩文件使用的扩展名
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zst",
+            Encoding::Gzip => "gz",
+        }
+    }
+
+    // 根据 Accept-Encoding 的 q 值选出最合适的编码
+    pub fn negotiate(req_headers: &HeaderMap) -> Option<Encoding> {
+        Encoding::ranked(req_headers).into_iter().next()
+    }
+
+    // 客户端接受的全部编码，按 q 值从高到低排列，q 值相同时按服务器偏好排列
+    pub fn ranked(req_headers: &HeaderMap) -> Vec<Encoding> {
+        let accept = req_headers
+            .get_all(header::ACCEPT_ENCODING)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+
+        let mut accepted: Vec<(Encoding, f32)> = Encoding::PREFERENCE
+            .into_iter()
+            .map(|encoding| (encoding, accept_quality(&accept, encoding)))
+            .filter(|(_, q)| *q > 0.0)
+            .collect();
+        // sort_by 是稳定排序，q 值相同的编码保持 PREFERENCE 中的顺序
+        accepted.sort_by(|a, b| b.1.total_cmp(&a.1));
+        accepted.into_iter().map(|(encoding, _)| encoding).collect()
+    }
+
+    // 同一资源不同编码的表示需要不同的 ETag
+    pub fn etag(&self, etag: &str) -> String {
+        match etag.strip_suffix('"') {
+            Some(prefix) => format!("{}-{}\"", prefix, self.as_str()),
+            None => etag.to_string(),
+        }
+    }
+
+    // 对任意异步读取源进行压缩并转为响应体
+    fn encode<R>(&self, reader: R) -> Body
+    where
+        R: tokio::io::AsyncBufRead + Send + Unpin + 'static,
+    {
+        match self {
+            Encoding::Brotli => Body::from_stream(ReaderStream::with_capacity(
+                BrotliEncoder::with_quality(reader, Level::Precise(4)),
+                CHUNK_SIZE,
+            )),
+            Encoding::Zstd => Body::from_stream(ReaderStream::with_capacity(
+                ZstdEncoder::with_quality(reader, Level::Default),
+                CHUNK_SIZE,
+            )),
+            Encoding::Gzip => Body::from_stream(ReaderStream::with_capacity(
+                GzipEncoder::with_quality(reader, Level::Default),
+                CHUNK_SIZE,
+            )),
+        }
+    }
+
+    // 边读文件边压缩
+    pub async fn encode_file(&self, file_path: &StdPath) -> std::io::Result<Body> {
+        let file = File::open(file_path).await?;
+        Ok(self.encode(BufReader::with_capacity(CHUNK_SIZE, file)))
+    }
+
+    // 压缩内存中已生成的内容（例如目录列表页面）
+    pub fn encode_bytes(&self, data: impl Into<Vec<u8>>) -> Body {
+        self.encode(Cursor::new(data.into()))
+    }
+
+    // 写入压缩响应相关的头；长度未知，去掉 Content-Length 改用分块传输
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(self.as_str()));
+        headers.remove(header::CONTENT_LENGTH);
+    }
+}
+
+// 取出 Accept-Encoding 中某个编码的 q 值，未列出时使用 `*` 的值
+fn accept_quality(accept: &str, encoding: Encoding) -> f32 {
+    let mut wildcard = None;
+    for item in accept.split(',') {
+        let mut parts = item.split(';');
+        let name = parts.next().unwrap_or_default().trim();
+        let q = parts
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        if name.eq_ignore_ascii_case(encoding.as_str())
+            || (encoding == Encoding::Gzip && name.eq_ignore_ascii_case("x-gzip"))
+        {
+            return q;
+        }
+        if name == "*" {
+            wildcard = Some(q);
+        }
+    }
+    wildcard.unwrap_or(0.0)
+}
+
+// 文本类内容才值得压缩，图片、视频、压缩包本身已经是压缩格式
+fn is_compressible(content_type: &str) -> bool {
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    mime.starts_with("text/")
+        || matches!(
+            mime,
+            "application/javascript"
+                | "application/json"
+                | "application/xml"
+                | "image/svg+xml"
+                | "image/x-icon"
+        )
+}
+
+// 与原文件同目录的预压缩版本，例如 app.js.br、app.js.gz
+pub struct Precompressed {
+    pub encoding: Encoding,
+    pub path: PathBuf,
+    pub metadata: std::fs::Metadata,
+}
+
+// 查找原文件的所有预压缩版本；比原文件旧的视为过期并忽略
+pub async fn precompressed_variants(file_path: &StdPath, original: &std::fs::Metadata) -> Vec<Precompressed> {
+    let mut variants = Vec::new();
+    for encoding in Encoding::PREFERENCE {
+        let mut path = file_path.as_os_str().to_owned();
+        path.push(".");
+        path.push(encoding.extension());
+        let path = PathBuf::from(path);
+
+        let Ok(metadata) = tokio::fs::metadata(&path).await else {
+            continue;
+        };
+        let stale = match (metadata.modified(), original.modified()) {
+            (Ok(sidecar), Ok(original)) => sidecar < original,
+            _ => false,
+        };
+        if metadata.is_file() && !stale {
+            variants.push(Precompressed {
+                encoding,
+                path,
+                metadata,
+            });
+        }
+    }
+    variants
+}
+
+// 按客户端偏好选出可用的预压缩版本
+pub fn select_precompressed<'a>(
+    req_headers: &HeaderMap,
+    variants: &'a [Precompressed],
+) -> Option<&'a Precompressed> {
+    Encoding::ranked(req_headers)
+        .into_iter()
+        .find_map(|encoding| variants.iter().find(|v| v.encoding == encoding))
+}
@@ -0,0 +1,216 @@
+use axum::http::{header, HeaderMap, HeaderValue};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// 修改时间距今不足该时长的文件可能仍在写入，只给出弱 ETag
+const WEAK_WINDOW: Duration = Duration::from_secs(1);
+
+// 响应的缓存校验器
+#[derive(Debug, Clone, Default)]
+pub struct Validators {
+    pub etag: Option<String>,
+    pub last_modified: Option<SystemTime>,
+}
+
+impl Validators {
+    // 根据文件大小和修改时间生成校验器
+    pub fn for_file(metadata: &std::fs::Metadata) -> Self {
+        let modified = metadata.modified().ok();
+        let etag = modified.map(|time| {
+            let nanos = time.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+            let tag = format!("\"{:x}-{:x}\"", metadata.len(), nanos);
+            let recent = SystemTime::now()
+                .duration_since(time)
+                .map(|age| age < WEAK_WINDOW)
+                .unwrap_or(true);
+            if recent {
+                format!("W/{}", tag)
+            } else {
+                tag
+            }
+        });
+        Validators {
+            etag,
+            last_modified: modified,
+        }
+    }
+
+    // 目录列表是动态生成的页面，只提供弱 ETag
+    pub fn for_listing<T: Hash>(entries: &T) -> Self {
+        let mut hasher = DefaultHasher::new();
+        entries.hash(&mut hasher);
+        Validators {
+            etag: Some(format!("W/\"{:x}\"", hasher.finish())),
+            last_modified: None,
+        }
+    }
+
+    // 写入 ETag / Last-Modified 响应头
+    pub fn apply(&self, headers: &mut HeaderMap) {
+        if let Some(value) = self.etag.as_deref().and_then(|v| HeaderValue::from_str(v).ok()) {
+            headers.insert(header::ETAG, value);
+        }
+        if let Some(time) = self.last_modified {
+            if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(time)) {
+                headers.insert(header::LAST_MODIFIED, value);
+            }
+        }
+    }
+
+    // If-Range 要求强比较：只有强 ETag 或精确到秒的修改时间才算匹配
+    pub fn if_range_matches(&self, value: &str) -> bool {
+        let value = value.trim();
+        if value.starts_with('"') || value.starts_with("W/") {
+            return match self.etag.as_deref() {
+                Some(etag) => strong_eq(etag, value),
+                None => false,
+            };
+        }
+        match (httpdate::parse_http_date(value), self.last_modified) {
+            (Ok(date), Some(modified)) => unix_secs(date) == unix_secs(modified),
+            _ => false,
+        }
+    }
+}
+
+// 前置条件的判断结果
+#[derive(Debug, PartialEq, Eq)]
+pub enum Precondition {
+    Proceed,
+    NotModified,
+    Failed,
+}
+
+// 按 RFC 9110 第 13.2.2 节的顺序评估 GET/HEAD 请求的条件头
+pub fn evaluate(req_headers: &HeaderMap, validators: &Validators) -> Precondition {
+    let etag = validators.etag.as_deref();
+
+    if let Some(if_match) = header_str(req_headers, header::IF_MATCH) {
+        let matched = list_matches(if_match, |tag| etag.is_some_and(|etag| strong_eq(etag, tag)));
+        if !matched {
+            return Precondition::Failed;
+        }
+    } else if let Some(date) = header_date(req_headers, header::IF_UNMODIFIED_SINCE) {
+        if let Some(modified) = validators.last_modified {
+            if unix_secs(modified) > unix_secs(date) {
+                return Precondition::Failed;
+            }
+        }
+    }
+
+    if let Some(if_none_match) = header_str(req_headers, header::IF_NONE_MATCH) {
+        let matched = list_matches(if_none_match, |tag| etag.is_some_and(|etag| weak_eq(etag, tag)));
+        if matched {
+            return Precondition::NotModified;
+        }
+    } else if let Some(date) = header_date(req_headers, header::IF_MODIFIED_SINCE) {
+        if let Some(modified) = validators.last_modified {
+            if unix_secs(modified) <= unix_secs(date) {
+                return Precondition::NotModified;
+            }
+        }
+    }
+
+    Precondition::Proceed
+}
+
+// `*` 匹配任何存在的表示，否则逐个比较逗号分隔的实体标签
+fn list_matches(value: &str, matches: impl Fn(&str) -> bool) -> bool {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .any(|tag| tag == "*" || matches(tag))
+}
+
+fn opaque_tag(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
+
+fn strong_eq(a: &str, b: &str) -> bool {
+    !a.starts_with("W/") && !b.starts_with("W/") && a == b
+}
+
+fn weak_eq(a: &str, b: &str) -> bool {
+    opaque_tag(a) == opaque_tag(b)
+}
+
+fn header_str(req_headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    req_headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+// 无法解析的日期按 RFC 要求忽略
+fn header_date(req_headers: &HeaderMap, name: header::HeaderName) -> Option<SystemTime> {
+    header_str(req_headers, name).and_then(|v| httpdate::parse_http_date(v.trim()).ok())
+}
+
+// HTTP 日期只有秒级精度，比较前统一截断
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(name.clone(), HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn validators(etag: &str) -> Validators {
+        Validators {
+            etag: Some(etag.to_string()),
+            last_modified: Some(UNIX_EPOCH + Duration::from_secs(1_700_000_000)),
+        }
+    }
+
+    #[test]
+    fn if_none_match_uses_weak_comparison() {
+        let strong = validators("\"abc\"");
+        for value in ["\"abc\"", "W/\"abc\"", "\"x\", \"abc\"", "*"] {
+            let request = headers(&[(header::IF_NONE_MATCH, value)]);
+            assert_eq!(evaluate(&request, &strong), Precondition::NotModified, "{}", value);
+        }
+        let request = headers(&[(header::IF_NONE_MATCH, "\"abc\"")]);
+        assert_eq!(evaluate(&request, &validators("W/\"abc\"")), Precondition::NotModified);
+        let request = headers(&[(header::IF_NONE_MATCH, "\"other\"")]);
+        assert_eq!(evaluate(&request, &strong), Precondition::Proceed);
+    }
+
+    #[test]
+    fn if_none_match_takes_precedence_over_if_modified_since() {
+        let request = headers(&[
+            (header::IF_NONE_MATCH, "\"other\""),
+            (header::IF_MODIFIED_SINCE, "Tue, 14 Nov 2023 22:13:20 GMT"),
+        ]);
+        assert_eq!(evaluate(&request, &validators("\"abc\"")), Precondition::Proceed);
+        let request = headers(&[(header::IF_MODIFIED_SINCE, "Tue, 14 Nov 2023 22:13:20 GMT")]);
+        assert_eq!(evaluate(&request, &validators("\"abc\"")), Precondition::NotModified);
+    }
+
+    #[test]
+    fn if_match_requires_strong_etag() {
+        let request = headers(&[(header::IF_MATCH, "\"abc\"")]);
+        assert_eq!(evaluate(&request, &validators("\"abc\"")), Precondition::Proceed);
+        assert_eq!(evaluate(&request, &validators("W/\"abc\"")), Precondition::Failed);
+    }
+
+    #[test]
+    fn if_range_matches_strong_etag_or_exact_date() {
+        let strong = validators("\"abc\"");
+        assert!(strong.if_range_matches("\"abc\""));
+        assert!(!strong.if_range_matches("W/\"abc\""));
+        assert!(!strong.if_range_matches("\"other\""));
+        assert!(!validators("W/\"abc\"").if_range_matches("W/\"abc\""));
+        assert!(strong.if_range_matches("Tue, 14 Nov 2023 22:13:20 GMT"));
+        assert!(!strong.if_range_matches("Tue, 14 Nov 2023 22:13:21 GMT"));
+        assert!(!strong.if_range_matches("not a date"));
+    }
+}
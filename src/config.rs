@@ -0,0 +1,688 @@
+use axum::http::HeaderValue;
+use serde::{Deserialize, Deserializer};
+use std::{
+    path::{Path as StdPath, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+use tower_http::cors::{AllowOrigin, Any, CorsLayer};
+
+use crate::{
+    accounts::{AccountsConfig, DEFAULT_SESSION_TTL},
+    auth::{AuthConfig, UserSpec, DEFAULT_REALM},
+    cli::{self, Cli, DEFAULT_PORT},
+    compression::{CompressionConfig, DEFAULT_MIN_SIZE},
+    listener::BindSpec,
+    mtls::ClientRule,
+    shares::ShareConfig,
+    tls::{Hsts, TlsConfig, TlsSource},
+    upload::{ConflictPolicy, UploadConfig, DEFAULT_MAX_REQUEST_SIZE},
+};
+
+// 未指定 --config 时自动加载的配置文件
+pub const DEFAULT_CONFIG_FILE: &str = "myhs.toml";
+
+const DEFAULT_BIND: &str = "0.0.0.0";
+const DEFAULT_LOG_FILTER: &str = "myhs=info";
+const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 30;
+
+type ConfigResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+// 最终生效的配置
+#[derive(Debug, Clone)]
+pub struct Config {
+    // 所有监听地址，未单独指定端口的使用 port
+    pub binds: Vec<BindSpec>,
+    pub port: u16,
+    // 端口被占用时自动尝试其他端口
+    pub port_auto: bool,
+    // 启动后把实际监听的端口等信息以 JSON 写入该文件
+    pub port_file: Option<PathBuf>,
+    // 收到停止信号后等待进行中请求完成的最长时间
+    pub shutdown_timeout: Duration,
+    // 启用 HTTPS 时的证书来源和客户端证书要求
+    pub tls: Option<TlsConfig>,
+    // 按路径限制可访问的客户端证书身份
+    pub client_rules: Vec<ClientRule>,
+    pub root: PathBuf,
+    pub read_only: bool,
+    pub upload: UploadConfig,
+    pub auth: AuthConfig,
+    // 多用户账号和路径权限
+    pub accounts: Option<AccountsConfig>,
+    // API 令牌文件
+    pub token_file: Option<PathBuf>,
+    // 分享链接
+    pub share: Option<ShareConfig>,
+    pub compression: CompressionConfig,
+    pub cors: CorsConfig,
+    pub log_filter: String,
+    // 实际加载的配置文件，用于启动时提示
+    pub source: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CorsConfig {
+    pub permissive: bool,
+    pub allow_origins: Vec<String>,
+}
+
+impl CorsConfig {
+    // 配置了来源列表时只允许这些来源，否则按 permissive 决定是否放开所有跨域请求；
+    // 两者都没有时（默认）不允许任何跨域来源
+    pub fn layer(&self) -> CorsLayer {
+        if !self.allow_origins.is_empty() {
+            let origins: Vec<HeaderValue> = self
+                .allow_origins
+                .iter()
+                .filter_map(|origin| HeaderValue::from_str(origin).ok())
+                .collect();
+            return CorsLayer::new()
+                .allow_origin(AllowOrigin::list(origins))
+                .allow_methods(Any)
+                .allow_headers(Any);
+        }
+        if self.permissive {
+            CorsLayer::permissive()
+        } else {
+            CorsLayer::new()
+        }
+    }
+}
+
+// 配置文件、环境变量、命令行三处来源共用的结构，所有字段都是可选的
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Settings {
+    server: ServerSettings,
+    tls: TlsSettings,
+    upload: UploadSettings,
+    auth: AuthSettings,
+    accounts: AccountsSettings,
+    tokens: TokensSettings,
+    share: ShareSettings,
+    compression: CompressionSettings,
+    cors: CorsSettings,
+    log: LogSettings,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ServerSettings {
+    #[serde(deserialize_with = "one_or_many")]
+    bind: Option<Vec<BindSpec>>,
+    port: Option<u16>,
+    port_auto: Option<bool>,
+    port_file: Option<PathBuf>,
+    root: Option<PathBuf>,
+    shutdown_timeout: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TlsSettings {
+    cert: Option<PathBuf>,
+    key: Option<PathBuf>,
+    self_signed: Option<bool>,
+    client_ca: Option<PathBuf>,
+    client_allow: Option<Vec<ClientRule>>,
+    #[serde(deserialize_with = "one_or_many")]
+    redirect_http: Option<Vec<BindSpec>>,
+    hsts_max_age: Option<u64>,
+    hsts_include_subdomains: Option<bool>,
+    hsts_preload: Option<bool>,
+    http3: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct UploadSettings {
+    enabled: Option<bool>,
+    #[serde(deserialize_with = "byte_size")]
+    max_request_size: Option<u64>,
+    #[serde(deserialize_with = "byte_size")]
+    max_file_size: Option<u64>,
+    fsync: Option<bool>,
+    on_conflict: Option<ConflictPolicy>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AuthSettings {
+    realm: Option<String>,
+    users: Option<Vec<UserSpec>>,
+    htpasswd: Option<PathBuf>,
+    upload_users: Option<Vec<UserSpec>>,
+    upload_htpasswd: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct AccountsSettings {
+    file: Option<PathBuf>,
+    session_ttl: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct TokensSettings {
+    file: Option<PathBuf>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct ShareSettings {
+    file: Option<PathBuf>,
+    key: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CompressionSettings {
+    enabled: Option<bool>,
+    min_size: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CorsSettings {
+    permissive: Option<bool>,
+    allow_origins: Option<Vec<String>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct LogSettings {
+    filter: Option<String>,
+}
+
+impl TlsSettings {
+    // 证书来源（证书、私钥、自签名开关）作为整体覆盖，避免高优先级的证书和低优先级的自签名开关混在一起
+    fn or(self, lower: TlsSettings) -> TlsSettings {
+        let has_source = self.cert.is_some() || self.key.is_some() || self.self_signed.is_some();
+        let (cert, key, self_signed) = if has_source {
+            (self.cert, self.key, self.self_signed)
+        } else {
+            (lower.cert, lower.key, lower.self_signed)
+        };
+        TlsSettings {
+            cert,
+            key,
+            self_signed,
+            client_ca: self.client_ca.or(lower.client_ca),
+            client_allow: self.client_allow.or(lower.client_allow),
+            redirect_http: self.redirect_http.or(lower.redirect_http),
+            hsts_max_age: self.hsts_max_age.or(lower.hsts_max_age),
+            hsts_include_subdomains: self.hsts_include_subdomains.or(lower.hsts_include_subdomains),
+            hsts_preload: self.hsts_preload.or(lower.hsts_preload),
+            http3: self.http3.or(lower.http3),
+        }
+    }
+}
+
+impl Settings {
+    // 读取 TOML 配置文件；相对路径按配置文件所在目录解析
+    fn from_file(path: &StdPath) -> ConfigResult<Self> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("无法读取配置文件 '{}': {}", path.display(), e))?;
+        let mut settings: Settings = toml::from_str(&text)
+            .map_err(|e| format!("配置文件 '{}' 格式错误: {}", path.display(), e))?;
+
+        if let Some(dir) = path.parent() {
+            let paths = [
+                settings.server.root.as_mut(),
+                settings.tls.cert.as_mut(),
+                settings.tls.key.as_mut(),
+                settings.tls.client_ca.as_mut(),
+                settings.auth.htpasswd.as_mut(),
+                settings.auth.upload_htpasswd.as_mut(),
+                settings.accounts.file.as_mut(),
+                settings.tokens.file.as_mut(),
+                settings.share.file.as_mut(),
+            ];
+            for path in paths.into_iter().flatten() {
+                if path.is_relative() {
+                    *path = dir.join(&*path);
+                }
+            }
+        }
+        Ok(settings)
+    }
+
+    // 读取 MYHS_* 环境变量
+    fn from_env() -> ConfigResult<Self> {
+        Ok(Settings {
+            server: ServerSettings {
+                bind: env_list("MYHS_BIND")?,
+                port: env_parse("MYHS_PORT")?,
+                port_auto: env_bool("MYHS_PORT_AUTO")?,
+                port_file: env_var("MYHS_PORT_FILE").map(PathBuf::from),
+                root: env_var("MYHS_ROOT").map(PathBuf::from),
+                shutdown_timeout: env_parse("MYHS_SHUTDOWN_TIMEOUT")?,
+            },
+            tls: TlsSettings {
+                cert: env_var("MYHS_TLS_CERT").map(PathBuf::from),
+                key: env_var("MYHS_TLS_KEY").map(PathBuf::from),
+                self_signed: env_bool("MYHS_TLS_SELF_SIGNED")?,
+                client_ca: env_var("MYHS_TLS_CLIENT_CA").map(PathBuf::from),
+                client_allow: env_rules("MYHS_TLS_CLIENT_ALLOW")?,
+                redirect_http: env_list("MYHS_REDIRECT_HTTP")?,
+                hsts_max_age: env_parse("MYHS_HSTS_MAX_AGE")?,
+                hsts_include_subdomains: env_bool("MYHS_HSTS_INCLUDE_SUBDOMAINS")?,
+                hsts_preload: env_bool("MYHS_HSTS_PRELOAD")?,
+                http3: env_bool("MYHS_HTTP3")?,
+            },
+            upload: UploadSettings {
+                enabled: env_bool("MYHS_UPLOAD")?,
+                max_request_size: env_size("MYHS_MAX_UPLOAD_SIZE")?,
+                max_file_size: env_size("MYHS_MAX_FILE_SIZE")?,
+                fsync: env_bool("MYHS_UPLOAD_FSYNC")?,
+                on_conflict: env_parse("MYHS_UPLOAD_CONFLICT")?,
+            },
+            auth: AuthSettings {
+                realm: env_var("MYHS_AUTH_REALM"),
+                users: env_list("MYHS_AUTH")?,
+                htpasswd: env_var("MYHS_AUTH_HTPASSWD").map(PathBuf::from),
+                upload_users: env_list("MYHS_UPLOAD_AUTH")?,
+                upload_htpasswd: env_var("MYHS_UPLOAD_HTPASSWD").map(PathBuf::from),
+            },
+            accounts: AccountsSettings {
+                file: env_var("MYHS_ACCOUNTS").map(PathBuf::from),
+                session_ttl: env_parse("MYHS_SESSION_TTL")?,
+            },
+            tokens: TokensSettings {
+                file: env_var("MYHS_TOKEN_FILE").map(PathBuf::from),
+            },
+            share: ShareSettings {
+                file: env_var("MYHS_SHARE_FILE").map(PathBuf::from),
+                key: env_var("MYHS_SHARE_KEY"),
+            },
+            compression: CompressionSettings {
+                enabled: env_bool("MYHS_COMPRESSION")?,
+                min_size: env_parse("MYHS_COMPRESSION_MIN_SIZE")?,
+            },
+            cors: CorsSettings {
+                permissive: env_bool("MYHS_CORS_PERMISSIVE")?,
+                allow_origins: env_var("MYHS_CORS_ORIGINS").map(|v| split_list(&v)),
+            },
+            log: LogSettings {
+                filter: env_var("MYHS_LOG").or_else(|| env_var("RUST_LOG")),
+            },
+        })
+    }
+
+    // 命令行中显式给出的参数
+    fn from_cli(cli: &Cli) -> Self {
+        Settings {
+            server: ServerSettings {
+                bind: (!cli.bind.is_empty()).then(|| cli.bind.clone()),
+                port: cli.port.or(cli.legacy_port),
+                port_auto: cli.port_auto.then_some(true),
+                port_file: cli.port_file.clone(),
+                root: cli.directory.clone().or_else(|| cli.legacy_directory.clone()),
+                shutdown_timeout: cli.shutdown_timeout,
+            },
+            tls: TlsSettings {
+                cert: cli.tls_cert.clone(),
+                key: cli.tls_key.clone(),
+                self_signed: cli.tls_self_signed.then_some(true),
+                client_ca: cli.tls_client_ca.clone(),
+                client_allow: (!cli.tls_client_allow.is_empty()).then(|| cli.tls_client_allow.clone()),
+                redirect_http: (!cli.redirect_http.is_empty()).then(|| cli.redirect_http.clone()),
+                hsts_max_age: cli.hsts_max_age,
+                hsts_include_subdomains: cli.hsts_include_subdomains.then_some(true),
+                hsts_preload: cli.hsts_preload.then_some(true),
+                http3: cli.http3.then_some(true),
+            },
+            upload: UploadSettings {
+                enabled: cli.read_only.then_some(false),
+                max_request_size: cli.max_upload_size,
+                max_file_size: cli.max_file_size,
+                fsync: cli.fsync_uploads.then_some(true),
+                on_conflict: cli.on_conflict,
+            },
+            auth: AuthSettings {
+                realm: cli.auth_realm.clone(),
+                users: (!cli.auth.is_empty()).then(|| cli.auth.clone()),
+                htpasswd: cli.htpasswd.clone(),
+                upload_users: (!cli.upload_auth.is_empty()).then(|| cli.upload_auth.clone()),
+                upload_htpasswd: cli.upload_htpasswd.clone(),
+            },
+            accounts: AccountsSettings {
+                file: cli.accounts.clone(),
+                session_ttl: cli.session_ttl,
+            },
+            tokens: TokensSettings {
+                file: cli.token_file.clone(),
+            },
+            // 签名密钥不接受命令行参数，避免出现在进程列表中
+            share: ShareSettings {
+                file: cli.share_file.clone(),
+                key: None,
+            },
+            compression: CompressionSettings {
+                enabled: cli.no_compression.then_some(false),
+                min_size: cli.compression_min_size,
+            },
+            cors: CorsSettings {
+                permissive: cli.cors_permissive.then_some(true),
+                allow_origins: (!cli.cors_origin.is_empty()).then(|| cli.cors_origin.clone()),
+            },
+            log: LogSettings {
+                filter: cli.log.clone(),
+            },
+        }
+    }
+
+    // 用优先级更低的 lower 补全本层缺失的字段
+    fn or(self, lower: Settings) -> Settings {
+        Settings {
+            server: ServerSettings {
+                bind: self.server.bind.or(lower.server.bind),
+                port: self.server.port.or(lower.server.port),
+                port_auto: self.server.port_auto.or(lower.server.port_auto),
+                port_file: self.server.port_file.or(lower.server.port_file),
+                root: self.server.root.or(lower.server.root),
+                shutdown_timeout: self.server.shutdown_timeout.or(lower.server.shutdown_timeout),
+            },
+            tls: self.tls.or(lower.tls),
+            upload: UploadSettings {
+                enabled: self.upload.enabled.or(lower.upload.enabled),
+                max_request_size: self.upload.max_request_size.or(lower.upload.max_request_size),
+                max_file_size: self.upload.max_file_size.or(lower.upload.max_file_size),
+                fsync: self.upload.fsync.or(lower.upload.fsync),
+                on_conflict: self.upload.on_conflict.or(lower.upload.on_conflict),
+            },
+            auth: AuthSettings {
+                realm: self.auth.realm.or(lower.auth.realm),
+                users: self.auth.users.or(lower.auth.users),
+                htpasswd: self.auth.htpasswd.or(lower.auth.htpasswd),
+                upload_users: self.auth.upload_users.or(lower.auth.upload_users),
+                upload_htpasswd: self.auth.upload_htpasswd.or(lower.auth.upload_htpasswd),
+            },
+            accounts: AccountsSettings {
+                file: self.accounts.file.or(lower.accounts.file),
+                session_ttl: self.accounts.session_ttl.or(lower.accounts.session_ttl),
+            },
+            tokens: TokensSettings {
+                file: self.tokens.file.or(lower.tokens.file),
+            },
+            share: ShareSettings {
+                file: self.share.file.or(lower.share.file),
+                key: self.share.key.or(lower.share.key),
+            },
+            compression: CompressionSettings {
+                enabled: self.compression.enabled.or(lower.compression.enabled),
+                min_size: self.compression.min_size.or(lower.compression.min_size),
+            },
+            cors: CorsSettings {
+                permissive: self.cors.permissive.or(lower.cors.permissive),
+                allow_origins: self.cors.allow_origins.or(lower.cors.allow_origins),
+            },
+            log: LogSettings {
+                filter: self.log.filter.or(lower.log.filter),
+            },
+        }
+    }
+}
+
+impl Config {
+    // 按 命令行 > 环境变量 > 配置文件 > 默认值 的优先级加载配置
+    pub fn load(cli: &Cli) -> ConfigResult<Config> {
+        let source = match cli.config.clone().or_else(|| env_var("MYHS_CONFIG").map(PathBuf::from)) {
+            Some(path) => Some(path),
+            None => {
+                let path = PathBuf::from(DEFAULT_CONFIG_FILE);
+                path.is_file().then_some(path)
+            }
+        };
+        let file = match &source {
+            Some(path) => Settings::from_file(path)?,
+            None => Settings::default(),
+        };
+
+        let settings = Settings::from_cli(cli).or(Settings::from_env()?).or(file);
+
+        let root = match settings.server.root {
+            Some(root) => root,
+            None => std::env::current_dir().map_err(|e| format!("无法获取当前目录: {}", e))?,
+        };
+        let port = settings.server.port.unwrap_or(DEFAULT_PORT);
+
+        let binds = settings
+            .server
+            .bind
+            .filter(|binds| !binds.is_empty())
+            .unwrap_or_else(|| vec![DEFAULT_BIND.parse().unwrap()]);
+
+        // 自签名优先；否则证书和私钥必须成对出现
+        let tls_source = match (settings.tls.self_signed.unwrap_or(false), settings.tls.cert, settings.tls.key) {
+            (true, _, _) => Some(TlsSource::SelfSigned),
+            (false, Some(cert), Some(key)) => Some(TlsSource::Files { cert, key }),
+            (false, None, None) => None,
+            (false, Some(_), None) => return Err("配置了 TLS 证书但缺少私钥（--tls-key / MYHS_TLS_KEY / [tls] key）".into()),
+            (false, None, Some(_)) => return Err("配置了 TLS 私钥但缺少证书（--tls-cert / MYHS_TLS_CERT / [tls] cert）".into()),
+        };
+        let redirect_http = settings.tls.redirect_http.unwrap_or_default();
+        let hsts = settings.tls.hsts_max_age.map(|max_age| Hsts {
+            max_age,
+            include_subdomains: settings.tls.hsts_include_subdomains.unwrap_or(false),
+            preload: settings.tls.hsts_preload.unwrap_or(false),
+        });
+        let http3 = settings.tls.http3.unwrap_or(false);
+        if http3 && !cfg!(feature = "http3") {
+            return Err("当前版本编译时未启用 HTTP/3，请使用 `cargo build --release --features http3` 重新编译".into());
+        }
+        let tls = match tls_source {
+            Some(source) => Some(TlsConfig {
+                source,
+                client_ca: settings.tls.client_ca,
+                redirect_http,
+                hsts,
+                http3,
+            }),
+            None if settings.tls.client_ca.is_some() => {
+                return Err("客户端证书认证需要先启用 HTTPS（--tls-cert / --tls-self-signed）".into())
+            }
+            None if !redirect_http.is_empty() => {
+                return Err("跳转到 HTTPS 需要先启用 HTTPS（--tls-cert / --tls-self-signed）".into())
+            }
+            None if hsts.is_some() => return Err("HSTS 需要先启用 HTTPS（--tls-cert / --tls-self-signed）".into()),
+            None if http3 => return Err("HTTP/3 需要先启用 HTTPS（--tls-cert / --tls-self-signed）".into()),
+            None => None,
+        };
+        let client_rules = settings.tls.client_allow.unwrap_or_default();
+        let client_auth = tls.as_ref().and_then(|tls| tls.client_ca.as_ref()).is_some();
+        if !client_rules.is_empty() && !client_auth {
+            return Err("按证书身份限制路径需要同时配置客户端 CA（--tls-client-ca）".into());
+        }
+
+        let auth = AuthConfig {
+            realm: settings.auth.realm.unwrap_or_else(|| DEFAULT_REALM.to_string()),
+            users: settings.auth.users.unwrap_or_default(),
+            htpasswd: settings.auth.htpasswd,
+            upload_users: settings.auth.upload_users.unwrap_or_default(),
+            upload_htpasswd: settings.auth.upload_htpasswd,
+        };
+        let accounts = settings.accounts.file.map(|file| AccountsConfig {
+            file,
+            session_ttl: Duration::from_secs(settings.accounts.session_ttl.unwrap_or(DEFAULT_SESSION_TTL)),
+            realm: auth.realm.clone(),
+        });
+        // 账号文件已经包含登录和权限控制，不能再叠加一层 Basic 认证
+        if accounts.is_some() && auth.is_enabled() {
+            return Err("账号文件（--accounts）不能与 --auth / --htpasswd 等 Basic 认证选项同时使用".into());
+        }
+
+        Ok(Config {
+            binds,
+            port,
+            port_auto: settings.server.port_auto.unwrap_or(false),
+            port_file: settings.server.port_file,
+            shutdown_timeout: Duration::from_secs(
+                settings.server.shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT),
+            ),
+            tls,
+            client_rules,
+            root,
+            read_only: !settings.upload.enabled.unwrap_or(true),
+            upload: UploadConfig {
+                max_request_size: settings.upload.max_request_size.unwrap_or(DEFAULT_MAX_REQUEST_SIZE),
+                max_file_size: settings.upload.max_file_size,
+                fsync: settings.upload.fsync.unwrap_or(false),
+                on_conflict: settings.upload.on_conflict.unwrap_or_default(),
+            },
+            auth,
+            accounts,
+            token_file: settings.tokens.file,
+            share: settings.share.file.map(|file| ShareConfig {
+                file,
+                key: settings.share.key,
+            }),
+            compression: CompressionConfig {
+                enabled: settings.compression.enabled.unwrap_or(true),
+                min_size: settings.compression.min_size.unwrap_or(DEFAULT_MIN_SIZE),
+            },
+            cors: CorsConfig {
+                permissive: settings.cors.permissive.unwrap_or(false),
+                allow_origins: settings.cors.allow_origins.unwrap_or_default(),
+            },
+            log_filter: settings.log.filter.unwrap_or_else(|| DEFAULT_LOG_FILTER.to_string()),
+            source,
+        })
+    }
+
+    // 不能通过服务器访问的文件；由子命令维护的文件写入时还会用到同名的 .tmp 临时文件和 .lock 锁文件
+    pub fn private_files(&self) -> Vec<PathBuf> {
+        let mut files: Vec<PathBuf> = self.source.iter().cloned().collect();
+        files.extend(self.auth.htpasswd.iter().chain(&self.auth.upload_htpasswd).cloned());
+        if let Some(TlsConfig { source: TlsSource::Files { key, .. }, .. }) = &self.tls {
+            files.push(key.clone());
+        }
+        let stores = self
+            .accounts
+            .as_ref()
+            .map(|accounts| &accounts.file)
+            .into_iter()
+            .chain(&self.token_file)
+            .chain(self.share.as_ref().map(|share| &share.file));
+        for file in stores {
+            files.push(file.clone());
+            files.push(file.with_extension("tmp"));
+            files.push(file.with_extension("lock"));
+        }
+        files
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+fn env_parse<T: FromStr>(name: &str) -> ConfigResult<Option<T>> {
+    match env_var(name) {
+        Some(value) => value
+            .trim()
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("环境变量 {} 的值 '{}' 无效", name, value).into()),
+        None => Ok(None),
+    }
+}
+
+fn env_bool(name: &str) -> ConfigResult<Option<bool>> {
+    match env_var(name) {
+        Some(value) => match value.trim().to_ascii_lowercase().as_str() {
+            "1" | "on" | "true" | "yes" => Ok(Some(true)),
+            "0" | "off" | "false" | "no" => Ok(Some(false)),
+            _ => Err(format!("环境变量 {} 的值 '{}' 不是有效的开关（on/off）", name, value).into()),
+        },
+        None => Ok(None),
+    }
+}
+
+fn env_size(name: &str) -> ConfigResult<Option<u64>> {
+    match env_var(name) {
+        Some(value) => cli::parse_size(&value)
+            .map(Some)
+            .map_err(|e| format!("环境变量 {} 的值无效: {}", name, e).into()),
+        None => Ok(None),
+    }
+}
+
+fn env_list<T: FromStr>(name: &str) -> ConfigResult<Option<Vec<T>>>
+where
+    T::Err: std::fmt::Display,
+{
+    match env_var(name) {
+        Some(value) => split_list(&value)
+            .iter()
+            .map(|item| item.parse::<T>())
+            .collect::<Result<Vec<T>, _>>()
+            .map(Some)
+            .map_err(|e| format!("环境变量 {} 的值无效: {}", name, e).into()),
+        None => Ok(None),
+    }
+}
+
+// 访问规则列表以分号分隔，例如 /releases=CN:ci;/private=DNS:admin.internal
+fn env_rules(name: &str) -> ConfigResult<Option<Vec<ClientRule>>> {
+    match env_var(name) {
+        Some(value) => value
+            .split(';')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<ClientRule>, _>>()
+            .map(Some)
+            .map_err(|e| format!("环境变量 {} 的值无效: {}", name, e).into()),
+        None => Ok(None),
+    }
+}
+
+// 配置文件中既可以写单个值也可以写数组，例如 bind = "::" 或 bind = ["127.0.0.1", "[::1]"]
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Option<Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(Some(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(value) => vec![value],
+        OneOrMany::Many(values) => values,
+    }))
+}
+
+// 大小既可以写字节数也可以写带单位的字符串，例如 max_file_size = 1048576 或 max_file_size = "1G"
+fn byte_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(bytes) => Ok(Some(bytes)),
+        Size::Text(text) => cli::parse_size(&text).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+// 逗号分隔的列表
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(String::from)
+        .collect()
+}
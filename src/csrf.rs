@@ -0,0 +1,210 @@
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    convert::Infallible,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use crate::{accounts, auth, tokens};
+
+// 保存 CSRF 令牌的 Cookie；页面中的表单把同一个值放在隐藏字段里提交
+const CSRF_COOKIE: &str = "myhs_csrf";
+
+// 表单中的隐藏字段名；脚本和其他客户端也可以改用 X-CSRF-Token 请求头
+pub const CSRF_FIELD: &str = "csrf_token";
+const CSRF_HEADER: &str = "x-csrf-token";
+
+// 令牌长度（十六进制字符数）
+const TOKEN_LEN: usize = 64;
+
+// 跨站请求防护：修改类请求检查 Origin / Referer / Sec-Fetch-Site，浏览器提交的表单还要带上 CSRF 令牌
+#[derive(Debug)]
+pub struct CsrfGuard {
+    // 只在 HTTPS 下发送 Cookie
+    secure: bool,
+    // 除本站外允许提交请求的来源，与 CORS 来源列表相同
+    trusted_origins: Vec<String>,
+}
+
+impl CsrfGuard {
+    pub fn new(secure: bool, trusted_origins: &[String]) -> CsrfGuard {
+        CsrfGuard {
+            secure,
+            trusted_origins: trusted_origins
+                .iter()
+                .map(|origin| origin.trim_end_matches('/').to_ascii_lowercase())
+                .collect(),
+        }
+    }
+
+    // 请求来源必须是本站或受信任的来源；两个头都没有的请求来自非浏览器客户端，交给令牌检查
+    fn check_origin(&self, headers: &HeaderMap, host: Option<&str>) -> Result<(), String> {
+        let source = header_str(headers, header::ORIGIN)
+            .map(str::to_string)
+            .or_else(|| header_str(headers, header::REFERER).and_then(origin_of));
+        let Some(source) = source else {
+            if header_str(headers, "sec-fetch-site") == Some("cross-site") {
+                return Err("跨站请求".to_string());
+            }
+            return Ok(());
+        };
+
+        let source = source.trim_end_matches('/').to_ascii_lowercase();
+        if self.trusted_origins.contains(&source) {
+            return Ok(());
+        }
+        // 只比较主机和端口：HTTPS 由反向代理终止时请求本身是 HTTP
+        let source_host = source.split_once("://").map(|(_, host)| host);
+        match (source_host, host) {
+            (Some(source_host), Some(host)) if same_host(source_host, &host.to_ascii_lowercase()) => Ok(()),
+            _ => Err(format!("来源 {} 不是本站", source)),
+        }
+    }
+
+    fn cookie(&self, token: &str) -> HeaderValue {
+        let secure = if self.secure { "; Secure" } else { "" };
+        HeaderValue::from_str(&format!(
+            "{}={}; Path=/; HttpOnly; SameSite=Lax{}",
+            CSRF_COOKIE, token, secure
+        ))
+        .unwrap()
+    }
+}
+
+// 当前请求的 CSRF 令牌，由中间件放入请求扩展；页面用它填写表单，处理器用它校验提交的字段
+#[derive(Debug, Clone, Default)]
+pub struct CsrfToken {
+    value: String,
+    // 浏览器提交的修改类请求需要在表单中带上令牌
+    required: bool,
+    // 响应的页面中有带令牌的表单；浏览器还没有 Cookie 时只在这种响应中下发
+    rendered: Arc<AtomicBool>,
+}
+
+impl CsrfToken {
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    // 本次请求是否需要在表单中提交令牌
+    pub fn required(&self) -> bool {
+        self.required
+    }
+
+    // 表单中的隐藏字段
+    pub fn form_field(&self) -> String {
+        self.rendered.store(true, Ordering::Relaxed);
+        format!(
+            r#"<input type="hidden" name="{}" value="{}">"#,
+            CSRF_FIELD,
+            accounts::html_escape(&self.value)
+        )
+    }
+
+    // 校验表单提交的令牌；失败时处理器返回 rejected()
+    pub fn verify(&self, submitted: Option<&str>) -> bool {
+        if !self.required || submitted.is_some_and(|submitted| self.matches(submitted)) {
+            return true;
+        }
+        tracing::info!("拒绝缺少有效 CSRF 令牌的请求");
+        false
+    }
+
+    fn matches(&self, submitted: &str) -> bool {
+        !self.value.is_empty() && auth::constant_time_eq(self.value.as_bytes(), submitted.as_bytes())
+    }
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for CsrfToken {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<CsrfToken>().cloned().unwrap_or_default())
+    }
+}
+
+// 为每个浏览器分配 CSRF 令牌，并拒绝来自其他站点的修改类请求
+pub async fn protect(State(guard): State<Arc<CsrfGuard>>, mut request: Request, next: Next) -> Response {
+    let headers = request.headers();
+    let cookie = accounts::cookie_value(headers, CSRF_COOKIE).filter(|token| valid_token(token));
+    let mutating = !matches!(*request.method(), Method::GET | Method::HEAD | Method::OPTIONS);
+
+    let mut token = CsrfToken {
+        value: cookie.clone().unwrap_or_else(|| tokens::random_hex(TOKEN_LEN / 2)),
+        required: false,
+        rendered: Arc::default(),
+    };
+    if mutating {
+        // HTTP/2 和 HTTP/3 的请求没有 Host 头，主机在 URI 中
+        let host = header_str(headers, header::HOST).or_else(|| request.uri().authority().map(|a| a.as_str()));
+        if let Err(reason) = guard.check_origin(headers, host) {
+            tracing::info!("拒绝 {} {}: {}", request.method(), request.uri().path(), reason);
+            return rejected();
+        }
+        // 只有浏览器会自动附带 Cookie 和登录凭据；使用 Bearer 令牌的客户端不受 CSRF 影响
+        let browser = [header::COOKIE, header::ORIGIN, header::HeaderName::from_static("sec-fetch-site")]
+            .iter()
+            .any(|name| headers.contains_key(name));
+        let bearer = header_str(headers, header::AUTHORIZATION)
+            .and_then(|value| value.split_once(' '))
+            .is_some_and(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"));
+        token.required = browser && !bearer;
+        if token.required && cookie.is_none() {
+            tracing::info!("拒绝没有 CSRF Cookie 的 {} {}", request.method(), request.uri().path());
+            return rejected();
+        }
+        if header_str(headers, CSRF_HEADER).is_some_and(|submitted| token.matches(submitted)) {
+            token.required = false;
+        }
+    }
+
+    // 下载、API 请求等没有表单的响应不设置 Cookie
+    let fresh = cookie.is_none();
+    let cookie_value = guard.cookie(&token.value);
+    let rendered = token.rendered.clone();
+    request.extensions_mut().insert(token);
+    let mut response = next.run(request).await;
+    if fresh && rendered.load(Ordering::Relaxed) {
+        response.headers_mut().append(header::SET_COOKIE, cookie_value);
+    }
+    response
+}
+
+pub fn rejected() -> Response {
+    (StatusCode::FORBIDDEN, "跨站请求校验失败，请刷新页面后重试").into_response()
+}
+
+fn valid_token(token: &str) -> bool {
+    token.len() == TOKEN_LEN && token.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+fn header_str(headers: &HeaderMap, name: impl header::AsHeaderName) -> Option<&str> {
+    headers.get(name)?.to_str().ok()
+}
+
+// Referer 中的 scheme://host[:port] 部分
+fn origin_of(referer: &str) -> Option<String> {
+    let (scheme, rest) = referer.split_once("://")?;
+    let host = rest.split(['/', '?', '#']).next()?;
+    Some(format!("{}://{}", scheme, host))
+}
+
+// 比较主机和端口；80 和 443 端口可能被省略
+fn same_host(source: &str, host: &str) -> bool {
+    let strip = |value: &str| {
+        value
+            .strip_suffix(":80")
+            .or_else(|| value.strip_suffix(":443"))
+            .unwrap_or(value)
+            .to_string()
+    };
+    strip(source) == strip(host)
+}
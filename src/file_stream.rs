@@ -0,0 +1,38 @@
+use axum::body::Body;
+use bytes::Bytes;
+use futures_util::{stream, Stream, StreamExt};
+use std::{io::SeekFrom, path::Path as StdPath, pin::Pin};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt},
+};
+use tokio_util::io::ReaderStream;
+
+// 每次从磁盘读取的块大小；客户端读得慢时不会继续读取，内存占用与文件大小无关
+pub const CHUNK_SIZE: usize = 64 * 1024;
+
+pub type ByteStream = Pin<Box<dyn Stream<Item = std::io::Result<Bytes>> + Send>>;
+
+// 打开文件中从 start 开始、长度为 len 的一段并转为字节流
+pub async fn open_stream(file_path: &StdPath, start: u64, len: u64) -> std::io::Result<ByteStream> {
+    let mut file = File::open(file_path).await?;
+    if start > 0 {
+        file.seek(SeekFrom::Start(start)).await?;
+    }
+    Ok(Box::pin(ReaderStream::with_capacity(file.take(len), CHUNK_SIZE)))
+}
+
+// 整个文件或其中一段作为响应体
+pub async fn file_body(file_path: &StdPath, start: u64, len: u64) -> std::io::Result<Body> {
+    Ok(Body::from_stream(open_stream(file_path, start, len).await?))
+}
+
+// 固定内容的字节流，用于 multipart 分隔符等
+pub fn bytes_stream(data: impl Into<Bytes>) -> ByteStream {
+    Box::pin(stream::once(std::future::ready(Ok(data.into()))))
+}
+
+// 依次拼接多个字节流
+pub fn concat(streams: Vec<ByteStream>) -> Body {
+    Body::from_stream(stream::iter(streams).flatten())
+}
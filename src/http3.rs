@@ -0,0 +1,152 @@
+use axum::{
+    body::{Body, HttpBody},
+    extract::Request,
+    http::HeaderValue,
+    Router,
+};
+use bytes::{Buf, Bytes};
+use h3::server::RequestResolver;
+use quinn::crypto::rustls::QuicServerConfig;
+use rustls::{pki_types::CertificateDer, ServerConfig};
+use std::{net::SocketAddr, pin::Pin, sync::Arc};
+use tokio::task::JoinSet;
+use tokio_util::sync::CancellationToken;
+use tower::Service;
+
+use crate::{listener, mtls::PeerIdentity};
+
+type H3Result<T> = Result<T, Box<dyn std::error::Error + Send + Sync>>;
+
+// 告诉 TCP 上的客户端同一端口还提供 HTTP/3
+pub fn alt_svc(port: u16) -> HeaderValue {
+    HeaderValue::from_str(&format!("h3=\":{}\"; ma=86400", port)).unwrap()
+}
+
+// 在 TCP 监听器的同一地址和端口上创建 QUIC 端点；证书与 HTTPS 相同，ALPN 改为 h3
+pub fn endpoint(tls: &ServerConfig, addr: SocketAddr, dual_stack: bool) -> std::io::Result<quinn::Endpoint> {
+    let mut tls = tls.clone();
+    tls.alpn_protocols = vec![b"h3".to_vec()];
+    let crypto = QuicServerConfig::try_from(tls).map_err(std::io::Error::other)?;
+    let server_config = quinn::ServerConfig::with_crypto(Arc::new(crypto));
+
+    let socket = listener::bind_udp(addr, dual_stack)?;
+    quinn::Endpoint::new(
+        quinn::EndpointConfig::default(),
+        Some(server_config),
+        socket,
+        Arc::new(quinn::TokioRuntime),
+    )
+}
+
+// 在 QUIC 端点上提供服务；stop 触发后不再接受新连接，等已有请求处理完后返回
+pub async fn serve(endpoint: quinn::Endpoint, app: Router, stop: CancellationToken) {
+    loop {
+        let incoming = tokio::select! {
+            incoming = endpoint.accept() => match incoming {
+                Some(incoming) => incoming,
+                None => break,
+            },
+            _ = stop.cancelled() => break,
+        };
+        tokio::spawn(serve_connection(incoming, app.clone(), stop.clone()));
+    }
+
+    endpoint.set_server_config(None);
+    endpoint.wait_idle().await;
+}
+
+async fn serve_connection(incoming: quinn::Incoming, app: Router, stop: CancellationToken) {
+    let remote_addr = incoming.remote_address();
+    let conn = match incoming.await {
+        Ok(conn) => conn,
+        Err(e) => {
+            tracing::debug!("与 {} 的 QUIC 握手失败: {}", remote_addr, e);
+            return;
+        }
+    };
+
+    // 客户端证书已由 TLS 层校验，身份随每个请求传给后续中间件
+    let identity = conn
+        .peer_identity()
+        .and_then(|identity| identity.downcast::<Vec<CertificateDer<'static>>>().ok())
+        .and_then(|certs| certs.first().and_then(|cert| PeerIdentity::from_der(cert)));
+
+    let mut h3_conn = match h3::server::Connection::new(h3_quinn::Connection::new(conn)).await {
+        Ok(h3_conn) => h3_conn,
+        Err(e) => {
+            tracing::debug!("与 {} 建立 HTTP/3 连接失败: {}", remote_addr, e);
+            return;
+        }
+    };
+
+    // 连接对象被丢弃时会立即关闭 QUIC 连接，所以要等本连接上的请求都处理完
+    let mut requests = JoinSet::new();
+    loop {
+        tokio::select! {
+            result = h3_conn.accept() => match result {
+                Ok(Some(resolver)) => {
+                    requests.spawn(serve_request(resolver, app.clone(), identity.clone()));
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    tracing::debug!("与 {} 的 HTTP/3 连接中断: {}", remote_addr, e);
+                    break;
+                }
+            },
+            Some(result) = requests.join_next() => {
+                if let Ok(Err(e)) = result {
+                    tracing::debug!("HTTP/3 请求处理失败: {}", e);
+                }
+            }
+            _ = stop.cancelled() => {
+                // 通知客户端不再接受新请求
+                let _ = h3_conn.shutdown(0).await;
+                break;
+            }
+        }
+    }
+    while requests.join_next().await.is_some() {}
+}
+
+async fn serve_request(
+    resolver: RequestResolver<h3_quinn::Connection, Bytes>,
+    mut app: Router,
+    identity: Option<PeerIdentity>,
+) -> H3Result<()> {
+    let (request, stream) = resolver.resolve_request().await?;
+    let (mut send, recv) = stream.split();
+
+    // 请求体按 DATA 帧流式读取，上传不会整体缓存在内存里
+    let body = Body::from_stream(futures_util::stream::unfold(Some(recv), |recv| async move {
+        let mut recv = recv?;
+        match recv.recv_data().await {
+            Ok(Some(mut data)) => Some((Ok(data.copy_to_bytes(data.remaining())), Some(recv))),
+            Ok(None) => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    }));
+    let mut request: Request = request.map(|()| body);
+    if let Some(identity) = identity {
+        request.extensions_mut().insert(identity);
+    }
+
+    let response = match app.call(request).await {
+        Ok(response) => response,
+        Err(infallible) => match infallible {},
+    };
+    let (parts, mut body) = response.into_parts();
+    send.send_response(axum::http::Response::from_parts(parts, ())).await?;
+
+    while let Some(frame) = std::future::poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+        match frame?.into_data() {
+            Ok(data) => send.send_data(data).await?,
+            Err(frame) => {
+                if let Ok(trailers) = frame.into_trailers() {
+                    send.send_trailers(trailers).await?;
+                }
+            }
+        }
+    }
+    send.finish().await?;
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+use socket2::{Domain, Protocol, Socket, Type};
+use std::{
+    fmt,
+    io::ErrorKind,
+    net::{IpAddr, SocketAddr},
+    str::FromStr,
+};
+use tokio::net::TcpListener;
+
+use crate::tls::Tls;
+
+const BACKLOG: i32 = 1024;
+
+// 自动选择端口时，在起始端口之后依次尝试的端口数，全部被占用后交给系统分配
+const PORT_AUTO_ATTEMPTS: u16 = 20;
+
+// 一个 --bind 值：只写地址时使用全局端口，也可以写成 地址:端口
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct BindSpec {
+    pub ip: IpAddr,
+    pub port: Option<u16>,
+}
+
+impl BindSpec {
+    pub fn socket_addr(&self, default_port: u16) -> SocketAddr {
+        SocketAddr::new(self.ip, self.port.unwrap_or(default_port))
+    }
+}
+
+impl FromStr for BindSpec {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if let Ok(addr) = value.parse::<SocketAddr>() {
+            return Ok(BindSpec {
+                ip: addr.ip(),
+                port: Some(addr.port()),
+            });
+        }
+        let ip = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')).unwrap_or(value);
+        match ip.parse::<IpAddr>() {
+            Ok(ip) => Ok(BindSpec { ip, port: None }),
+            Err(_) => Err(format!(
+                "'{}' 不是有效的监听地址，应为 IP 地址或 地址:端口，例如 127.0.0.1、[::]、[::1]:8080",
+                value
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for BindSpec {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+// 已绑定的监听器
+pub struct BoundListener {
+    pub listener: TcpListener,
+    pub local_addr: SocketAddr,
+    // [::] 是否同时接受 IPv4 连接
+    pub dual_stack: bool,
+    // 是否使用全局端口（而不是 地址:端口 中单独指定的端口）
+    pub global_port: bool,
+}
+
+impl BoundListener {
+    // 通过该监听器实际可以访问到的本机地址
+    pub fn ips(&self) -> Vec<IpAddr> {
+        match self.local_addr.ip() {
+            IpAddr::V4(ip) if ip.is_unspecified() => interface_ips(false, true),
+            IpAddr::V6(ip) if ip.is_unspecified() => interface_ips(true, self.dual_stack),
+            ip => vec![ip],
+        }
+    }
+
+    // 列出通过该监听器实际可以访问到的 URL
+    pub fn urls(&self, scheme: &str) -> Vec<String> {
+        let port = self.local_addr.port();
+        self.ips()
+            .into_iter()
+            .map(|ip| format!("{}://{}", scheme, SocketAddr::new(ip, port)))
+            .collect()
+    }
+}
+
+// 监听失败的地址及原因
+#[derive(Debug)]
+pub struct BindError {
+    pub addr: SocketAddr,
+    pub source: std::io::Error,
+    // 该地址是否使用全局端口（自动选择端口时只有这类地址可以换端口重试）
+    global_port: bool,
+    // 是否已经开启了 --port-auto，开启时不再提示使用它
+    port_auto: bool,
+}
+
+impl fmt::Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hint = match self.source.kind() {
+            ErrorKind::AddrInUse if self.port_auto => "端口已被其他程序占用",
+            ErrorKind::AddrInUse => "端口已被其他程序占用，可以换一个端口或使用 --port-auto 自动选择",
+            ErrorKind::PermissionDenied => "权限不足，1024 以下的端口通常需要管理员权限",
+            ErrorKind::AddrNotAvailable => "本机没有这个地址，请检查 --bind 参数",
+            _ => "",
+        };
+        write!(f, "无法监听 {}: {}", self.addr, self.source)?;
+        if !hint.is_empty() {
+            write!(f, "（{}）", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for BindError {}
+
+// 绑定所有监听地址；开启 port_auto 时端口被占用会依次尝试后面的端口，最后交给系统分配
+pub fn bind_all(binds: &[BindSpec], port: u16, port_auto: bool) -> Result<Vec<BoundListener>, BindError> {
+    if !port_auto {
+        return bind_with_port(binds, port);
+    }
+
+    let last = port.saturating_add(PORT_AUTO_ATTEMPTS);
+    for candidate in port..=last {
+        match bind_with_port(binds, candidate) {
+            Err(e) if e.global_port && e.source.kind() == ErrorKind::AddrInUse => continue,
+            result => return result.map_err(|e| BindError { port_auto: true, ..e }),
+        }
+    }
+    bind_with_port(binds, 0).map_err(|e| BindError { port_auto: true, ..e })
+}
+
+// 用同一个全局端口绑定所有地址；端口为 0 时，第一个监听器拿到的端口供其余地址共用
+// 同一端口上同时监听 0.0.0.0 和 [::] 时，[::] 只接受 IPv6
+fn bind_with_port(binds: &[BindSpec], mut port: u16) -> Result<Vec<BoundListener>, BindError> {
+    let mut listeners: Vec<BoundListener> = Vec::with_capacity(binds.len());
+    for spec in binds {
+        let addr = spec.socket_addr(port);
+        if addr.port() != 0 && listeners.iter().any(|l| l.local_addr == addr) {
+            continue;
+        }
+
+        let ipv4_taken = binds
+            .iter()
+            .any(|other| other.ip.is_ipv4() && other.ip.is_unspecified() && other.port.unwrap_or(port) == addr.port());
+        let dual_stack = addr.ip().is_unspecified() && addr.is_ipv6() && !ipv4_taken;
+
+        let mut bound = bind(addr, dual_stack).map_err(|source| BindError {
+            addr,
+            source,
+            global_port: spec.port.is_none(),
+            port_auto: false,
+        })?;
+        bound.global_port = spec.port.is_none();
+        if bound.global_port && port == 0 {
+            port = bound.local_addr.port();
+        }
+        listeners.push(bound);
+    }
+    Ok(listeners)
+}
+
+// 创建 TCP 监听器；IPv6 地址显式设置 IPV6_V6ONLY，避免依赖系统默认值
+pub fn bind(addr: SocketAddr, dual_stack: bool) -> std::io::Result<BoundListener> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&addr.into())?;
+    socket.listen(BACKLOG)?;
+
+    let listener = TcpListener::from_std(socket.into())?;
+    let local_addr = listener.local_addr()?;
+    Ok(BoundListener {
+        listener,
+        local_addr,
+        dual_stack,
+        global_port: false,
+    })
+}
+
+// 创建与 TCP 监听器同地址同端口的 UDP 套接字，供 HTTP/3 使用
+#[cfg(feature = "http3")]
+pub fn bind_udp(addr: SocketAddr, dual_stack: bool) -> std::io::Result<std::net::UdpSocket> {
+    let socket = Socket::new(Domain::for_address(addr), Type::DGRAM, Some(Protocol::UDP))?;
+    if addr.is_ipv6() {
+        socket.set_only_v6(!dual_stack)?;
+    }
+    socket.bind(&addr.into())?;
+    Ok(socket.into())
+}
+
+// 本机网卡地址，回环地址排在最前；IPv6 链路本地地址需要区域标识，无法直接放进 URL，跳过
+fn interface_ips(ipv6: bool, ipv4: bool) -> Vec<IpAddr> {
+    let mut ips: Vec<IpAddr> = if_addrs::get_if_addrs()
+        .unwrap_or_default()
+        .into_iter()
+        .map(|iface| iface.ip())
+        .filter(|ip| match ip {
+            IpAddr::V4(_) => ipv4,
+            IpAddr::V6(v6) => ipv6 && (v6.segments()[0] & 0xffc0) != 0xfe80,
+        })
+        .collect();
+    ips.sort_by_key(|ip| (!ip.is_loopback(), ip.is_ipv6(), *ip));
+    ips.dedup();
+    ips
+}
+
+// 启动信息，供脚本和测试程序获取实际监听的端口
+#[derive(Debug, Serialize)]
+pub struct StartupInfo {
+    pub pid: u32,
+    pub port: u16,
+    pub listen: Vec<SocketAddr>,
+    pub urls: Vec<String>,
+    // 启用 HTTPS 时证书的 SHA-256 指纹
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tls_fingerprint: Option<String>,
+}
+
+impl StartupInfo {
+    // port 取第一个使用全局端口的监听器，没有时取第一个监听器
+    pub fn new(listeners: &[BoundListener], tls: Option<&Tls>) -> Self {
+        let port = listeners
+            .iter()
+            .find(|l| l.global_port)
+            .or(listeners.first())
+            .map(|l| l.local_addr.port())
+            .unwrap_or(0);
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        StartupInfo {
+            pid: std::process::id(),
+            port,
+            listen: listeners.iter().map(|l| l.local_addr).collect(),
+            urls: listeners.iter().flat_map(|l| l.urls(scheme)).collect(),
+            tls_fingerprint: tls.map(|tls| tls.fingerprint.clone()),
+        }
+    }
+}
@@ -1,55 +1,192 @@
 use axum::{
-    extract::{Path, Multipart},
-    http::{StatusCode, HeaderMap, header},
+    extract::{DefaultBodyLimit, Path, Multipart, Query},
+    http::{StatusCode, HeaderMap, HeaderValue, Method, header},
     response::{Html, Response, IntoResponse},
     routing::{get, post},
     Router,
 };
+use clap::Parser;
 use std::{
+    collections::HashMap,
     path::{Path as StdPath, PathBuf},
-    env,
-    io::Write,
+    sync::Arc,
 };
+use futures_util::{future::BoxFuture, FutureExt};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
 use tokio::fs;
+use tokio_rustls::TlsAcceptor;
 use tower::ServiceBuilder;
-use tower_http::{
-    cors::CorsLayer,
-    trace::TraceLayer,
-};
+use tower_http::{set_header::SetResponseHeaderLayer, trace::TraceLayer};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
+mod accounts;
+mod auth;
+mod cli;
+mod compression;
+mod conditional;
+mod config;
+mod csrf;
+mod listener;
+mod mtls;
+mod private;
+mod file_stream;
+#[cfg(feature = "http3")]
+mod http3;
+mod range;
+mod redirect;
+mod server;
+mod shares;
+mod shutdown;
+mod store;
+mod tls;
+mod tokens;
+mod upload;
+
+use accounts::{html_escape, Accounts, Permission, Principal};
+use auth::BasicAuth;
+use cli::{Cli, Command};
+use compression::CompressionConfig;
+use conditional::{Precondition, Validators};
+use config::Config;
+use csrf::{CsrfGuard, CsrfToken};
+use mtls::{ClientRules, PeerIdentity};
+use private::PrivateFiles;
+use range::RangeRequest;
+use shares::{ShareError, ShareStore};
+use shutdown::{Drain, InFlight};
+use tls::Tls;
+use tokens::{ApiToken, TokenStore};
+use tokio_util::sync::CancellationToken;
+use upload::{ConflictPolicy, PartialUploads, SaveOptions, UploadConfig, UploadError, UploadReport};
+
+// 所有处理器共享的服务器状态
+struct AppState {
+    base_dir: PathBuf,
+    read_only: bool,
+    compression: CompressionConfig,
+    upload: UploadConfig,
+    uploads: Arc<PartialUploads>,
+    // 按路径限制的客户端证书身份
+    client_rules: Arc<ClientRules>,
+    // 多用户账号；未配置时不按用户限制
+    accounts: Option<Arc<Accounts>>,
+    // API 令牌
+    tokens: Option<Arc<TokenStore>>,
+    // 分享链接
+    shares: Option<Arc<ShareStore>>,
+    // 配置文件等不对外提供的文件
+    private: Arc<PrivateFiles>,
+}
+
+impl AppState {
+    // 上传临时文件和配置文件等不对外提供，当作不存在处理
+    fn is_hidden(&self, path: &StdPath) -> bool {
+        is_upload_temp(path) || self.private.contains(path)
+    }
+
+    // API 令牌只按自身的范围和路径授权；否则按账号文件中的规则检查，未配置账号文件时所有请求都允许
+    fn permits(&self, caller: &Caller, path: &str, permission: Permission) -> bool {
+        match &caller.token {
+            Some(token) => token.allows(path, permission),
+            None => self
+                .accounts
+                .as_ref()
+                .is_none_or(|accounts| accounts.permits(caller.principal.as_ref(), path, permission)),
+        }
+    }
+
+    // 没有权限时的响应
+    fn denied(&self, caller: &Caller, headers: &HeaderMap, path: &str) -> Response {
+        match (&caller.token, &self.accounts) {
+            (Some(token), _) => tokens::forbidden(token, path),
+            (None, Some(accounts)) => accounts.denied(caller.principal.as_ref(), headers, path),
+            // 只有令牌和账号文件会拒绝请求，不会走到这里
+            (None, None) => StatusCode::FORBIDDEN.into_response(),
+        }
+    }
+}
+
+// 发起请求的身份：登录用户或 API 令牌，由认证中间件放入请求扩展
+#[derive(Debug, Clone, Default)]
+struct Caller {
+    principal: Option<Principal>,
+    token: Option<ApiToken>,
+}
+
+#[axum::async_trait]
+impl<S: Send + Sync> axum::extract::FromRequestParts<S> for Caller {
+    type Rejection = std::convert::Infallible;
 
+    async fn from_request_parts(parts: &mut axum::http::request::Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Caller {
+            principal: parts.extensions.get::<Principal>().cloned(),
+            token: parts.extensions.get::<ApiToken>().cloned(),
+        })
+    }
+}
+
+#[derive(Hash)]
 struct FileInfo {
     name: String,
-    is_dir: bool,
     size: Option<u64>,
-    modified: Option<String>,
+    modified: Option<std::time::SystemTime>,
+}
+
+// 生成好的目录列表页面及其 ETag
+struct DirectoryListing {
+    html: String,
+    validators: Validators,
 }
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Completions { shell }) => {
+            cli::print_completions(shell);
+            return;
+        }
+        Some(Command::HashPassword) => {
+            if let Err(e) = cli::print_password_hash() {
+                eprintln!("错误: {}", e);
+                std::process::exit(1);
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    // 合并命令行、环境变量和配置文件
+    let config = match Config::load(&cli) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(Command::Token { action }) = &cli.command {
+        if let Err(e) = cli::run_token_command(action, config.token_file.as_deref()) {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(Command::Share { action }) = &cli.command {
+        if let Err(e) = cli::run_share_command(action, config.share.as_ref(), &config.root) {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     // 初始化日志
     tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::try_from_default_env()
-                .unwrap_or_else(|_| "myhs=info".into()),
-        )
+        .with(tracing_subscriber::EnvFilter::new(&config.log_filter))
         .with(tracing_subscriber::fmt::layer())
         .init();
 
-    // 获取命令行参数或使用当前目录
-    let args: Vec<String> = env::args().collect();
-    let serve_dir = if args.len() > 1 {
-        PathBuf::from(&args[1])
-    } else {
-        env::current_dir().unwrap()
-    };
-
-    let port = if args.len() > 2 {
-        args[2].parse().unwrap_or(2333)
-    } else {
-        2333
-    };
+    let serve_dir = config.root.clone();
 
     // 验证目录是否存在
     if !serve_dir.exists() || !serve_dir.is_dir() {
@@ -57,93 +194,769 @@ async fn main() {
         std::process::exit(1);
     }
 
+    let listeners = match listener::bind_all(&config.binds, config.port, config.port_auto) {
+        Ok(listeners) => listeners,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // 加载证书；自签名证书覆盖所有监听地址
+    let tls = match &config.tls {
+        Some(tls_config) => {
+            let hosts: Vec<_> = listeners.iter().flat_map(|l| l.ips()).collect();
+            match Tls::load(tls_config, &hosts) {
+                Ok(tls) => Some(tls),
+                Err(e) => {
+                    eprintln!("错误: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        None => None,
+    };
+
+    // 加载账号和 htpasswd 文件；没有配置账号时不要求登录
+    let basic_auth = match BasicAuth::load(&config.auth) {
+        Ok(basic_auth) => Arc::new(basic_auth),
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // 加载账号文件；会话 Cookie 只在 HTTPS 下发送
+    let accounts = match &config.accounts {
+        Some(accounts_config) => match Accounts::load(accounts_config, tls.is_some()) {
+            Ok(accounts) => Some(Arc::new(accounts)),
+            Err(e) => {
+                eprintln!("错误: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // 打开 API 令牌文件；之后文件被修改时自动重新读取
+    let token_store = match &config.token_file {
+        Some(path) => match TokenStore::open(path) {
+            Ok(store) => Some(Arc::new(store)),
+            Err(e) => {
+                eprintln!("错误: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // 打开分享链接文件，同样在文件被修改时自动重新读取
+    let share_store = match &config.share {
+        Some(share_config) => match ShareStore::open(share_config) {
+            Ok(store) => Some(Arc::new(store)),
+            Err(e) => {
+                eprintln!("错误: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // 只负责跳转到 HTTPS 的 HTTP 监听器
+    let redirect_http = config.tls.as_ref().map(|tls| tls.redirect_http.as_slice()).unwrap_or_default();
+    let redirect_listeners = match listener::bind_all(redirect_http, redirect::DEFAULT_REDIRECT_PORT, false) {
+        Ok(listeners) => listeners,
+        Err(e) => {
+            eprintln!("错误: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // HTTP/3：在每个 HTTPS 监听器的同一地址和端口上监听 UDP
+    #[cfg(feature = "http3")]
+    let quic_endpoints = match &tls {
+        Some(tls) if config.tls.as_ref().is_some_and(|tls| tls.http3) => {
+            let endpoints = listeners
+                .iter()
+                .map(|bound| {
+                    http3::endpoint(&tls.config, bound.local_addr, bound.dual_stack)
+                        .map_err(|e| format!("无法在 UDP {} 上监听 HTTP/3: {}", bound.local_addr, e))
+                })
+                .collect::<Result<Vec<_>, _>>();
+            match endpoints {
+                Ok(endpoints) => endpoints,
+                Err(e) => {
+                    eprintln!("错误: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => Vec::new(),
+    };
+
+    // 实际监听的端口，供脚本和测试程序读取
+    let startup = listener::StartupInfo::new(&listeners, tls.as_ref());
+    let startup_json = serde_json::to_string(&startup).unwrap();
+    if let Some(port_file) = &config.port_file {
+        if let Err(e) = std::fs::write(port_file, format!("{}\n", startup_json)) {
+            eprintln!("错误: 无法写入端口文件 '{}': {}", port_file.display(), e);
+            std::process::exit(1);
+        }
+    }
+
+    let state = Arc::new(AppState {
+        base_dir: serve_dir,
+        read_only: config.read_only,
+        compression: config.compression,
+        upload: config.upload,
+        uploads: Arc::new(PartialUploads::default()),
+        client_rules: Arc::new(ClientRules::new(config.client_rules.clone())),
+        accounts,
+        tokens: token_store,
+        shares: share_store,
+        private: Arc::new(PrivateFiles::new(&config.private_files())),
+    });
+
+    // 清理上次运行被强制结束时遗留的上传临时文件；目录很大时遍历较慢，放在后台进行
+    if !state.read_only {
+        let state = state.clone();
+        tokio::task::spawn_blocking(move || {
+            let removed = state.uploads.remove_stale(&state.base_dir);
+            if removed > 0 {
+                tracing::info!("已删除 {} 个上次遗留的上传临时文件", removed);
+            }
+        });
+    }
+
+    if cli.json {
+        println!("{}", startup_json);
+    } else {
+        print_banner(&config, &state, &listeners, &redirect_listeners, &startup, tls.as_ref());
+    }
+    let in_flight = Arc::new(InFlight::default());
+
+    // 构建应用路由；配置了账号文件时加上登录页
+    let mut app = Router::new()
+        .route("/", get(serve_handler))
+        .route("/*path", get(serve_handler).delete(delete_handler))
+        .route("/upload", post(upload_handler));
+    if let Some(accounts) = &state.accounts {
+        app = app.merge(accounts::router(accounts.clone()));
+    }
+    let mut app = app.layer(
+        ServiceBuilder::new()
+            .layer(axum::middleware::from_fn_with_state(
+                state.client_rules.clone(),
+                mtls::authorize,
+            ))
+            .layer(axum::middleware::from_fn_with_state(
+                state.tokens.clone(),
+                tokens::authenticate,
+            ))
+            .layer(axum::middleware::from_fn_with_state(basic_auth, auth::require_basic))
+            .layer(axum::middleware::from_fn_with_state(
+                state.accounts.clone(),
+                accounts::identify,
+            )),
+    );
+    // 分享链接和上传链接由链接本身授权，不经过上面的认证中间件
+    if state.shares.is_some() {
+        app = app.merge(
+            Router::new()
+                .route("/s/:token", get(share_handler))
+                .route("/s/:token/*path", get(share_handler))
+                .route("/u/:token", get(upload_link_page).post(upload_link_handler)),
+        );
+    }
+    let app = app
+        .layer(
+            ServiceBuilder::new()
+                .layer(axum::middleware::from_fn_with_state(
+                    in_flight.clone(),
+                    shutdown::track_in_flight,
+                ))
+                .layer(TraceLayer::new_for_http().make_span_with(server::request_span))
+                .layer(config.cors.layer())
+                .layer(DefaultBodyLimit::max(
+                    usize::try_from(config.upload.max_request_size).unwrap_or(usize::MAX),
+                ))
+                .layer(axum::middleware::from_fn_with_state(
+                    Arc::new(CsrfGuard::new(tls.is_some(), &config.cors.allow_origins)),
+                    csrf::protect,
+                )),
+        )
+        .with_state(state.clone());
+
+    // HSTS 只加在 HTTPS 响应上；跳转监听器使用单独的 Router
+    let app = match config.tls.as_ref().and_then(|tls| tls.hsts) {
+        Some(hsts) => app.layer(SetResponseHeaderLayer::overriding(
+            header::STRICT_TRANSPORT_SECURITY,
+            hsts.header_value(),
+        )),
+        None => app,
+    };
+
+    // 收到 Ctrl+C / SIGTERM 后所有监听器停止接受新连接
+    let stop = CancellationToken::new();
+    tokio::spawn({
+        let stop = stop.clone();
+        async move {
+            shutdown::signal().await;
+            stop.cancel();
+        }
+    });
+
+    // 所有监听地址共用同一个 Router
+    let acceptor = tls.map(|tls| TlsAcceptor::from(tls.config));
+    let mut servers: Vec<BoxFuture<'static, ()>> = Vec::new();
+    for bound in listeners {
+        // 提供 HTTP/3 时在 TCP 响应中通告同一端口的 Alt-Svc
+        #[cfg(feature = "http3")]
+        let app = if quic_endpoints.is_empty() {
+            app.clone()
+        } else {
+            app.clone().layer(SetResponseHeaderLayer::overriding(
+                header::ALT_SVC,
+                http3::alt_svc(bound.local_addr.port()),
+            ))
+        };
+        #[cfg(not(feature = "http3"))]
+        let app = app.clone();
+        servers.push(server::serve(bound.listener, app, acceptor.clone(), stop.clone()).boxed());
+    }
+    #[cfg(feature = "http3")]
+    for endpoint in quic_endpoints {
+        servers.push(http3::serve(endpoint, app.clone(), stop.clone()).boxed());
+    }
+    let redirect_app = redirect::router(startup.port);
+    for bound in redirect_listeners {
+        servers.push(server::serve(bound.listener, redirect_app.clone(), None, stop.clone()).boxed());
+    }
+    let servers = futures_util::future::join_all(servers);
+    tokio::pin!(servers);
+
+    // 停止信号优先：收到信号后服务也会很快退出，两者可能同时就绪
+    tokio::select! {
+        biased;
+        _ = stop.cancelled() => {}
+        _ = &mut servers => return,
+    }
+
+    // 停止过程的提示写到标准错误，--json 时标准输出只有启动信息那一行
+    eprintln!(
+        "\n🛑 收到停止信号，不再接受新连接，等待 {} 个进行中的请求完成（最多 {} 秒，再次按 Ctrl+C 立即退出）",
+        in_flight.count(),
+        config.shutdown_timeout.as_secs()
+    );
+    match shutdown::drain(servers, config.shutdown_timeout, &in_flight).await {
+        Drain::Completed => eprintln!("✅ 所有请求已完成"),
+        Drain::TimedOut(remaining) => eprintln!("⚠️ 等待超时，放弃 {} 个未完成的请求", remaining),
+        Drain::Forced(remaining) => eprintln!("⚠️ 再次收到停止信号，放弃 {} 个未完成的请求", remaining),
+    }
+
+    // 清理被中断的上传留下的半截文件
+    let removed = state.uploads.cleanup();
+    if removed > 0 {
+        eprintln!("🧹 已删除 {} 个未完成的上传文件", removed);
+    }
+    eprintln!("👋 服务器已停止");
+}
+
+// 打印启动信息
+fn print_banner(
+    config: &Config,
+    state: &AppState,
+    listeners: &[listener::BoundListener],
+    redirect_listeners: &[listener::BoundListener],
+    startup: &listener::StartupInfo,
+    tls: Option<&Tls>,
+) {
     println!("🌐 Python风格的HTTP文件服务器");
-    println!("📁 服务目录: {}", serve_dir.display());
-    println!("🚀 服务器地址: http://0.0.0.0:{}", port);
+    println!("📁 服务目录: {}", state.base_dir.display());
+    if let Some(source) = &config.source {
+        println!("⚙️ 配置文件: {}", source.display());
+    }
+    println!("🚀 服务器地址:");
+    let scheme = if tls.is_some() { "https" } else { "http" };
+    for bound in listeners {
+        for url in bound.urls(scheme) {
+            println!("   {}", url);
+        }
+    }
+    // 端口 0 本来就由系统分配，不算改用了其他端口
+    if config.port != 0 && startup.port != config.port && listeners.iter().any(|l| l.global_port) {
+        println!("⚠️ 端口 {} 不可用，已自动改用端口 {}", config.port, startup.port);
+    }
+    if let Some(tls) = tls {
+        if tls.self_signed {
+            println!("🔒 HTTPS 已启用（自签名证书，浏览器会提示不受信任）");
+        } else {
+            println!("🔒 HTTPS 已启用");
+        }
+        println!("   SHA-256 指纹: {}", tls.fingerprint);
+        for url in redirect_listeners.iter().flat_map(|l| l.urls("http")) {
+            println!("↪️ {} 跳转到 HTTPS", url);
+        }
+        if config.tls.as_ref().is_some_and(|tls| tls.http3) {
+            println!("⚡ HTTP/3 已启用（同一端口的 UDP）");
+        }
+        if let Some(hsts) = config.tls.as_ref().and_then(|tls| tls.hsts) {
+            println!("📌 HSTS: {}", hsts.header_value().to_str().unwrap());
+        }
+        if tls.client_auth {
+            if config.client_rules.is_empty() {
+                println!("🪪 要求客户端证书");
+            } else {
+                println!("🪪 要求客户端证书，{} 条路径规则", config.client_rules.len());
+            }
+        }
+    }
+    if config.auth.is_enabled() {
+        let read = !config.auth.users.is_empty() || config.auth.htpasswd.is_some();
+        let upload = !config.auth.upload_users.is_empty() || config.auth.upload_htpasswd.is_some();
+        match (read, upload) {
+            (true, true) => println!("🔑 浏览和上传使用不同的账号"),
+            (true, false) => println!("🔑 访问需要登录"),
+            _ => println!("🔑 上传需要登录"),
+        }
+        if tls.is_none() {
+            println!("⚠️ 未启用 HTTPS，密码将以明文传输");
+        }
+    }
+    if let Some(accounts) = &state.accounts {
+        println!(
+            "👥 账号文件: {}（{} 个用户，{} 条路径规则，登录页 /login）",
+            config.accounts.as_ref().unwrap().file.display(),
+            accounts.user_count(),
+            accounts.rule_count()
+        );
+        if tls.is_none() {
+            println!("⚠️ 未启用 HTTPS，密码和会话 Cookie 将以明文传输");
+        }
+    }
+    if let (Some(store), Some(path)) = (&state.tokens, &config.token_file) {
+        println!("🎫 API 令牌: {}（{} 个有效）", path.display(), store.active_count());
+    }
+    if let (Some(store), Some(share)) = (&state.shares, &config.share) {
+        let (downloads, uploads) = store.active_counts();
+        println!(
+            "🔗 分享链接: {}（{} 个下载链接 /s/<令牌>，{} 个上传链接 /u/<令牌> 有效）",
+            share.file.display(),
+            downloads,
+            uploads
+        );
+    }
     println!("📋 功能:");
     println!("   • 目录浏览");
     println!("   • 文件下载");
-    println!("   • 文件上传");
+    if config.read_only {
+        println!("   • 只读模式（已禁用上传）");
+    } else {
+        match config.upload.max_file_size {
+            Some(max_file_size) => println!(
+                "   • 文件上传（单个请求最大 {}，单个文件最大 {}）",
+                format_file_size(config.upload.max_request_size),
+                format_file_size(max_file_size)
+            ),
+            None => println!("   • 文件上传（单个请求最大 {}）", format_file_size(config.upload.max_request_size)),
+        }
+    }
     println!("   • 自动索引页面");
     println!("   • 文件信息显示");
     println!("\n按 Ctrl+C 停止服务器\n");
+}
 
-    // 构建应用路由
-    let app = Router::new()
-        .route("/", get(serve_handler))
-        .route("/*path", get(serve_handler))
-        .route("/upload", post(upload_handler))
-        .layer(
-            ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
-                .layer(CorsLayer::permissive()),
-        )
-        .with_state(serve_dir);
-
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
-        .await
-        .unwrap();
-
-    axum::serve(listener, app).await.unwrap();
+fn is_upload_temp(path: &StdPath) -> bool {
+    path.file_name()
+        .is_some_and(|name| upload::is_temp_file(&name.to_string_lossy()))
 }
 
 // 主要的文件服务处理器
 async fn serve_handler(
     path: Option<Path<String>>,
-    axum::extract::State(base_dir): axum::extract::State<PathBuf>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    caller: Caller,
+    csrf: CsrfToken,
+    req_headers: HeaderMap,
 ) -> impl IntoResponse {
-    let path_str = path.map(|Path(p)| p).unwrap_or_default();
+    let base_dir = &state.base_dir;
+    // 先规范化，去掉 .. 之后不会离开基础目录；权限检查和读取文件使用同一个路径
+    let url_path = mtls::normalize_path(&path.map(|Path(p)| p).unwrap_or_default());
+    let path_str = url_path.trim_start_matches('/');
     let requested_path = if path_str.is_empty() {
         base_dir.clone()
     } else {
-        base_dir.join(&path_str)
+        base_dir.join(path_str)
     };
 
     // 安全检查：防止路径遍历攻击
-    if !requested_path.starts_with(&base_dir) {
+    if !upload::is_within(base_dir, &requested_path) {
         return (StatusCode::FORBIDDEN, "访问被拒绝").into_response();
     }
 
-    if !requested_path.exists() {
+    // 目录需要列目录权限，文件（包括不存在的路径）需要读取权限，避免泄露文件是否存在
+    let permission = if requested_path.is_dir() { Permission::List } else { Permission::Read };
+    if !state.permits(&caller, &url_path, permission) {
+        return state.denied(&caller, &req_headers, &url_path);
+    }
+
+    // 正在写入的上传临时文件和配置文件等不可下载
+    if !requested_path.exists() || state.is_hidden(&requested_path) {
         return (StatusCode::NOT_FOUND, "文件或目录不存在").into_response();
     }
 
     if requested_path.is_dir() {
         // 如果是目录，生成目录列表页面
-        match generate_directory_listing(&requested_path, &base_dir, &path_str).await {
-            Ok(html) => Html(html).into_response(),
+        let upload = (!state.read_only && state.permits(&caller, &url_path, Permission::Upload))
+            .then_some(state.upload.on_conflict);
+        // 通过令牌访问时路径前缀以内全部可见，只有登录用户需要按条目过滤
+        let viewer = match (&state.accounts, &caller.token) {
+            (Some(accounts), None) => Some((accounts.as_ref(), caller.principal.as_ref())),
+            _ => None,
+        };
+        match generate_directory_listing(&requested_path, path_str, "", upload, viewer, &state.private, &csrf).await {
+            Ok(listing) => listing_response(listing, &state.compression, &req_headers),
             Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "无法读取目录").into_response(),
         }
     } else {
         // 如果是文件，提供文件下载
-        match serve_file(&requested_path).await {
+        match serve_file(&requested_path, &req_headers, &state.compression).await {
             Ok(response) => response,
             Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "无法读取文件").into_response(),
         }
     }
 }
 
-// 生成目录列表页面
+// 分享链接：只校验链接本身，可以访问分享的文件，或分享目录中的文件和子目录
+async fn share_handler(
+    Path(params): Path<HashMap<String, String>>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    method: Method,
+    req_headers: HeaderMap,
+) -> Response {
+    let Some(shares) = &state.shares else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let token = &params["token"];
+    let shared = match shares.resolve(token).await {
+        Ok(shared) => shared,
+        Err(ShareError::Invalid) => return (StatusCode::NOT_FOUND, "分享链接不存在或已被吊销").into_response(),
+        Err(_) => return (StatusCode::GONE, "分享链接已过期或下载次数已用完").into_response(),
+    };
+
+    // 子路径先规范化，去掉 .. 之后不会离开分享的路径
+    let sub_path = params.get("path").map(|path| mtls::normalize_path(path)).unwrap_or_default();
+    let sub_path = sub_path.trim_start_matches('/');
+    let shared_path = state.base_dir.join(shared.path.trim_start_matches('/'));
+    let requested_path = if sub_path.is_empty() {
+        shared_path.clone()
+    } else {
+        shared_path.join(sub_path)
+    };
+    if !requested_path.starts_with(&state.base_dir) || !requested_path.exists() || state.is_hidden(&requested_path) {
+        return (StatusCode::NOT_FOUND, "文件或目录不存在").into_response();
+    }
+    // 分享单个文件时只能访问这个文件本身
+    if !sub_path.is_empty() && !shared_path.is_dir() {
+        return (StatusCode::NOT_FOUND, "文件或目录不存在").into_response();
+    }
+
+    if requested_path.is_dir() {
+        let link_prefix = format!("/s/{}", token);
+        return match generate_directory_listing(
+            &requested_path,
+            sub_path,
+            &link_prefix,
+            None,
+            None,
+            &state.private,
+            &CsrfToken::default(),
+        )
+        .await
+        {
+            Ok(listing) => listing_response(listing, &state.compression, &req_headers),
+            Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, "无法读取目录").into_response(),
+        };
+    }
+
+    // 限制了下载次数的链接忽略 Range：多段或不从头开始的范围请求可以分几次取完文件，不能按次计数
+    let mut req_headers = req_headers;
+    if shared.limited {
+        req_headers.remove(header::RANGE);
+        req_headers.remove(header::IF_RANGE);
+    }
+    let mut response = match serve_file(&requested_path, &req_headers, &state.compression).await {
+        Ok(response) => response,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, "无法读取文件").into_response(),
+    };
+    if shared.limited {
+        response.headers_mut().remove(header::ACCEPT_RANGES);
+    }
+    // 返回文件内容的 GET 都计入次数；HEAD 和 304 不计
+    let sends_body = matches!(response.status(), StatusCode::OK | StatusCode::PARTIAL_CONTENT);
+    if method == Method::GET && sends_body {
+        if shared.limited {
+            // 没能记下这次下载时不返回文件，否则下载次数的上限会失效
+            match shares.record_download(&shared.id).await {
+                Ok(()) => {}
+                Err(ShareError::Invalid) => return (StatusCode::NOT_FOUND, "分享链接不存在或已被吊销").into_response(),
+                Err(ShareError::Unavailable) => {
+                    return (StatusCode::SERVICE_UNAVAILABLE, "暂时无法记录下载次数，请稍后重试").into_response()
+                }
+                Err(_) => return (StatusCode::GONE, "分享链接已过期或下载次数已用完").into_response(),
+            }
+        }
+        tracing::info!("分享链接 {} 下载 {}", shared.id, requested_path.display());
+    }
+    response
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct UploadLinkQuery {
+    uploaded: Option<usize>,
+    total: Option<usize>,
+}
+
+// 上传链接的页面：只有上传表单，不列出目录内容
+async fn upload_link_page(
+    Path(token): Path<String>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    Query(query): Query<UploadLinkQuery>,
+    csrf: CsrfToken,
+) -> Response {
+    let Some(shares) = &state.shares else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let notice = match (query.uploaded, query.total) {
+        (Some(uploaded), Some(total)) if uploaded == total => Some(format!("{} 个文件上传成功", uploaded)),
+        (Some(uploaded), Some(total)) => Some(format!("{} 个文件中的 {} 个上传成功", total, uploaded)),
+        _ => None,
+    };
+    match shares.resolve_upload(&token).await {
+        Ok(target) => Html(shares::render_upload_page(&token, Some(&target), notice.as_deref(), &csrf)).into_response(),
+        Err(ShareError::Invalid) => (StatusCode::NOT_FOUND, "上传链接不存在或已被吊销").into_response(),
+        // 刚用完额度的上传者仍能看到上传结果
+        Err(_) => (
+            StatusCode::GONE,
+            Html(shares::render_upload_page(&token, None, notice.as_deref(), &csrf)),
+        )
+            .into_response(),
+    }
+}
+
+// 通过上传链接上传：上传者看不到目录内容，同名文件总是改名保存，结果中只有是否保存成功，
+// 不返回最终的文件名；每个文件完整收到后占用链接的额度
+async fn upload_link_handler(
+    Path(token): Path<String>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    csrf: CsrfToken,
+    req_headers: HeaderMap,
+    mut multipart: Multipart,
+) -> Response {
+    let Some(shares) = &state.shares else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let target = match shares.resolve_upload(&token).await {
+        Ok(target) => target,
+        Err(ShareError::Invalid) => return (StatusCode::NOT_FOUND, "上传链接不存在或已被吊销").into_response(),
+        Err(_) => return (StatusCode::GONE, "上传链接已过期或额度已用完").into_response(),
+    };
+    if state.read_only {
+        return (StatusCode::FORBIDDEN, "服务器处于只读模式，已禁用上传").into_response();
+    }
+    // 浏览器提交的表单先带 CSRF 令牌，其他客户端可以直接发送文件
+    let fields = if csrf.required() {
+        match read_form_fields(&mut multipart, csrf::CSRF_FIELD).await {
+            Ok(fields) => fields,
+            Err(response) => return response,
+        }
+    } else {
+        HashMap::new()
+    };
+    if !csrf.verify(fields.get(csrf::CSRF_FIELD).map(String::as_str)) {
+        return csrf::rejected();
+    }
+    let target_dir = state.base_dir.join(target.path.trim_start_matches('/'));
+    if !upload::is_within(&state.base_dir, &target_dir) || !target_dir.is_dir() {
+        return (StatusCode::NOT_FOUND, "接收文件的目录不存在").into_response();
+    }
+
+    // 单个文件不能超过链接剩余的容量；文件完整收到后再按实际大小占用额度
+    let max_file_size = match (state.upload.max_file_size, target.remaining_bytes) {
+        (Some(limit), Some(remaining)) => Some(limit.min(remaining)),
+        (limit, remaining) => limit.or(remaining),
+    };
+    // 跳过、报错和子目录都会让上传者探知目录中已有哪些文件
+    let options = SaveOptions {
+        max_file_size,
+        fsync: state.upload.fsync,
+        on_conflict: ConflictPolicy::Rename,
+        conflict_field: false,
+        folders: false,
+        private: &state.private,
+    };
+    let link_id = target.id.as_str();
+    let saved = upload::save_files(
+        &state.uploads,
+        &mut multipart,
+        &target_dir,
+        options,
+        |_, permission| permission == Permission::Upload,
+        |_, size| async move {
+            match shares.reserve_upload(link_id, size).await {
+                Ok(()) => Ok(()),
+                Err(ShareError::TooLarge) => Err("超过上传链接剩余的容量".to_string()),
+                Err(ShareError::Unavailable) => Err("服务器暂时无法记录上传额度".to_string()),
+                Err(_) => Err("上传链接已过期或额度已用完".to_string()),
+            }
+        },
+    )
+    .await;
+    let report = match saved {
+        Ok(report) => report.hide_names(),
+        Err(e) => return e.into_response(),
+    };
+    tracing::info!("上传链接 {} 收到 {}/{} 个文件", target.id, report.saved(), report.total());
+
+    let back = format!("/u/{}", token);
+    let location = format!("{}?uploaded={}&total={}", back, report.saved(), report.total());
+    upload_response(&report, &req_headers, &back, location)
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct UploadQuery {
+    on_conflict: Option<String>,
+}
+
+// 同名文件的处理方式：表单字段优先，其次是查询参数，都没有时使用服务器的默认设置
+fn conflict_policy(
+    fields: &HashMap<String, String>,
+    query: &UploadQuery,
+    default: ConflictPolicy,
+) -> Result<ConflictPolicy, String> {
+    match fields.get("on_conflict").or(query.on_conflict.as_ref()) {
+        Some(value) if !value.trim().is_empty() => value.parse(),
+        _ => Ok(default),
+    }
+}
+
+// 请求 JSON 时返回每个文件的结果；浏览器在所有文件按原名保存时重定向到 location，否则显示结果页
+fn upload_response(report: &UploadReport, req_headers: &HeaderMap, back: &str, location: String) -> Response {
+    let json = req_headers
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("application/json"));
+    if json {
+        return (
+            report.status(),
+            [(header::CONTENT_TYPE, "application/json; charset=utf-8")],
+            report.to_json(),
+        )
+            .into_response();
+    }
+    if report.all_saved() {
+        let message = match report.total() {
+            0 => "没有收到文件".to_string(),
+            1 => "文件上传成功".to_string(),
+            total => format!("所有{}个文件上传成功", total),
+        };
+        return (StatusCode::SEE_OTHER, [(header::LOCATION, location)], message).into_response();
+    }
+    (report.status(), Html(upload::render_report(report, back))).into_response()
+}
+
+// 目录列表的响应，处理压缩和条件请求
+fn listing_response(mut listing: DirectoryListing, compression: &CompressionConfig, req_headers: &HeaderMap) -> Response {
+    let content_type = "text/html; charset=utf-8";
+    let encoding = compression.negotiate(req_headers, content_type, listing.html.len() as u64);
+    if let Some(encoding) = encoding {
+        listing.validators.etag = listing.validators.etag.map(|etag| encoding.etag(&etag));
+    }
+
+    let mut headers = HeaderMap::new();
+    listing.validators.apply(&mut headers);
+    if compression.varies(content_type) {
+        compression::add_vary(&mut headers);
+    }
+    match conditional::evaluate(req_headers, &listing.validators) {
+        Precondition::Proceed => match encoding {
+            Some(encoding) => {
+                encoding.apply(&mut headers);
+                headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+                (headers, encoding.encode_bytes(listing.html)).into_response()
+            }
+            None => (headers, Html(listing.html)).into_response(),
+        },
+        Precondition::NotModified => (StatusCode::NOT_MODIFIED, headers).into_response(),
+        Precondition::Failed => StatusCode::PRECONDITION_FAILED.into_response(),
+    }
+}
+
+// 链接中的路径段除字母数字和 - . _ ~ 以外全部转义，文件名中的引号、& 等字符不会破坏 HTML 属性
+const SEGMENT_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC.remove(b'-').remove(b'.').remove(b'_').remove(b'~');
+
+// 逐段转义路径，保留分隔的 /
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| utf8_percent_encode(segment, SEGMENT_ESCAPE).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+// 上传表单中同名文件处理方式的选项，默认选中服务器的设置
+fn conflict_options(selected: ConflictPolicy) -> String {
+    [
+        (ConflictPolicy::Overwrite, "覆盖"),
+        (ConflictPolicy::Rename, "保留两者（自动编号）"),
+        (ConflictPolicy::Skip, "跳过"),
+        (ConflictPolicy::Fail, "报错"),
+    ]
+    .iter()
+    .map(|(policy, label)| {
+        format!(
+            r#"<option value="{}"{}>{}</option>"#,
+            policy.name(),
+            if *policy == selected { " selected" } else { "" },
+            label
+        )
+    })
+    .collect()
+}
+
+// 生成目录列表页面；link_prefix 是链接的前缀，分享链接中为 /s/<令牌>
 async fn generate_directory_listing(
     dir_path: &StdPath,
-    _base_dir: &StdPath,
     current_path: &str,
-) -> Result<String, Box<dyn std::error::Error>> {
+    link_prefix: &str,
+    upload: Option<ConflictPolicy>,
+    viewer: Option<(&Accounts, Option<&Principal>)>,
+    private: &PrivateFiles,
+    csrf: &CsrfToken,
+) -> Result<DirectoryListing, Box<dyn std::error::Error>> {
     let mut entries = fs::read_dir(dir_path).await?;
     let mut files = Vec::new();
     let mut dirs = Vec::new();
 
     while let Some(entry) = entries.next_entry().await? {
-        let metadata = entry.metadata().await?;
+        // 上传临时文件随时可能被改名或删除，在读取元数据之前跳过；配置文件等同样不列出
         let name = entry.file_name().to_string_lossy().to_string();
+        if upload::is_temp_file(&name) || private.contains(&dir_path.join(&name)) {
+            continue;
+        }
+        let metadata = entry.metadata().await?;
+
+        // 配置了账号时只列出当前用户有权限的条目
+        if let Some((accounts, principal)) = viewer {
+            let entry_path = format!("/{}/{}", current_path, name);
+            if !accounts.can_see(principal, &entry_path, metadata.is_dir()) {
+                continue;
+            }
+        }
         
         let file_info = FileInfo {
             name: name.clone(),
-            is_dir: metadata.is_dir(),
             size: if metadata.is_file() { Some(metadata.len()) } else { None },
-            modified: metadata.modified().ok().map(|time| {
-                format!("{:?}", time)
-            }),
+            modified: metadata.modified().ok(),
         };
 
         if metadata.is_dir() {
@@ -157,10 +970,24 @@ async fn generate_directory_listing(
     dirs.sort_by(|a, b| a.name.cmp(&b.name));
     files.sort_by(|a, b| a.name.cmp(&b.name));
 
+    // 列表内容只取决于路径、当前用户、表单中的 CSRF 令牌、上传设置和各条目的名称、大小、修改时间；
+    // 没有上传和退出表单的页面不含令牌，也不会下发令牌的 Cookie，不能让每次请求的新令牌改变 ETag
+    let user = viewer.and_then(|(_, principal)| principal).map(|p| p.user.as_str());
+    let has_form = upload.is_some() || user.is_some();
+    let validators = Validators::for_listing(&(
+        current_path,
+        link_prefix,
+        user,
+        has_form.then(|| csrf.value()),
+        upload,
+        &dirs,
+        &files,
+    ));
+
     let title = if current_path.is_empty() {
         "目录索引 /".to_string()
     } else {
-        format!("目录索引 /{}", current_path)
+        format!("目录索引 /{}", html_escape(current_path))
     };
 
     let parent_link = if current_path.is_empty() {
@@ -171,22 +998,35 @@ async fn generate_directory_listing(
             .map(|p| p.to_string_lossy().to_string())
             .unwrap_or_default();
         format!(
-            "<tr><td><a href='/{}'><strong>📁 ../</strong></a></td><td>-</td><td>目录</td></tr>",
-            parent_path
+            "<tr><td><a href='{}/{}'><strong>📁 ../</strong></a></td><td>-</td><td>目录</td></tr>",
+            link_prefix,
+            encode_path(&parent_path)
         )
     };
 
-    // 添加文件上传表单
-    let upload_form = format!(r#"
+    // 添加文件上传表单，只读模式下不显示
+    let upload_form = match upload {
+        None => String::new(),
+        Some(on_conflict) => format!(r#"
     <div class="upload-container">
         <h3>📤 文件上传</h3>
         <form id="uploadForm" action="/upload" method="post" enctype="multipart/form-data">
+            {}
+            <div class="conflict-option">
+                <label for="conflictSelect">同名文件</label>
+                <select id="conflictSelect" name="on_conflict">{}</select>
+            </div>
             <input type="hidden" name="current_path" value="{}">
             <div class="upload-box">
                 <div class="file-input-container">
                     <input type="file" id="fileInput" name="file" class="file-input" multiple>
                     <label for="fileInput" class="file-label">选择文件</label>
                 </div>
+                <div class="file-input-container">
+                    <input type="file" id="folderInput" class="file-input" webkitdirectory multiple>
+                    <label for="folderInput" class="file-label">选择文件夹</label>
+                </div>
+                <span class="upload-hint">也可以把文件或文件夹拖到这里</span>
                 <div class="file-actions">
                     <button type="button" id="clearButton" class="clear-button" style="display:none;">清除全部</button>
                     <button type="submit" class="upload-button">上传</button>
@@ -197,7 +1037,22 @@ async fn generate_directory_listing(
             </div>
         </form>
     </div>
-    "#, current_path);
+    "#, csrf.form_field(), conflict_options(on_conflict), html_escape(current_path)),
+    };
+
+    // 配置了账号时显示当前用户和登录、退出入口
+    let account_bar = match viewer {
+        None => String::new(),
+        Some((_, Some(principal))) => format!(
+            r#"<div class="account">👤 {} <form action="/logout" method="post">{}<button type="submit">退出</button></form></div>"#,
+            accounts::html_escape(&principal.user),
+            csrf.form_field()
+        ),
+        Some((_, None)) => format!(
+            r#"<div class="account">未登录 <a href="/login?next=/{}">登录</a></div>"#,
+            utf8_percent_encode(current_path, NON_ALPHANUMERIC)
+        ),
+    };
 
     let mut file_rows = String::new();
     
@@ -209,8 +1064,10 @@ async fn generate_directory_listing(
             format!("{}/{}", current_path, dir.name)
         };
         file_rows.push_str(&format!(
-            "<tr><td><a href='/{}'><strong>📁 {}/</strong></a></td><td>-</td><td>目录</td></tr>",
-            link_path, dir.name
+            "<tr><td><a href='{}/{}'><strong>📁 {}/</strong></a></td><td>-</td><td>目录</td></tr>",
+            link_prefix,
+            encode_path(&link_path),
+            html_escape(&dir.name)
         ));
     }
 
@@ -221,14 +1078,17 @@ async fn generate_directory_listing(
         } else {
             format!("{}/{}", current_path, file.name)
         };
-        let size_str = file.size.map_or("-".to_string(), |s| format_file_size(s));
+        let size_str = file.size.map_or("-".to_string(), format_file_size);
         file_rows.push_str(&format!(
-            "<tr><td><a href='/{}'><strong>📄 {}</strong></a></td><td>{}</td><td>文件</td></tr>",
-            link_path, file.name, size_str
+            "<tr><td><a href='{}/{}'><strong>📄 {}</strong></a></td><td>{}</td><td>文件</td></tr>",
+            link_prefix,
+            encode_path(&link_path),
+            html_escape(&file.name),
+            size_str
         ));
     }
 
-    Ok(format!(r#"
+    let html = format!(r#"
 <!DOCTYPE html>
 <html lang="zh-CN">
 <head>
@@ -260,6 +1120,26 @@ async fn generate_directory_listing(
             margin: 0;
             font-size: 1.8rem;
         }}
+        .account {{
+            display: flex;
+            justify-content: flex-end;
+            align-items: center;
+            gap: 10px;
+            padding: 10px 20px;
+            border-bottom: 1px solid #dee2e6;
+            color: #495057;
+        }}
+        .account form {{
+            margin: 0;
+        }}
+        .account button {{
+            background: none;
+            border: 1px solid #6c757d;
+            border-radius: 4px;
+            color: #495057;
+            cursor: pointer;
+            padding: 2px 10px;
+        }}
         .path {{
             background: #f8f9fa;
             padding: 15px 20px;
@@ -307,6 +1187,19 @@ async fn generate_directory_listing(
             margin-top: 0;
             color: #495057;
         }}
+        .conflict-option {{
+            display: flex;
+            align-items: center;
+            gap: 8px;
+            margin-bottom: 10px;
+            color: #495057;
+            font-size: 0.9rem;
+        }}
+        .conflict-option select {{
+            padding: 4px 8px;
+            border: 1px solid #ced4da;
+            border-radius: 4px;
+        }}
         .upload-box {{
             display: flex;
             align-items: center;
@@ -317,6 +1210,14 @@ async fn generate_directory_listing(
             border-radius: 8px;
             background-color: white;
         }}
+        .upload-box.dragging {{
+            border-color: #007bff;
+            background-color: #f0f7ff;
+        }}
+        .upload-hint {{
+            color: #6c757d;
+            font-size: 0.9rem;
+        }}
         .file-input-container {{
             position: relative;
         }}
@@ -381,6 +1282,22 @@ async fn generate_directory_listing(
             text-overflow: ellipsis;
             padding-right: 10px;
         }}
+        .file-item.result {{
+            grid-template-columns: 1fr auto;
+        }}
+        .file-item-status {{
+            font-size: 0.9em;
+            text-align: right;
+        }}
+        .file-item-status.saved {{
+            color: #1e7e34;
+        }}
+        .file-item-status.skipped {{
+            color: #856404;
+        }}
+        .file-item-status.failed {{
+            color: #c82333;
+        }}
         .file-item-size {{
             color: #6c757d;
             font-size: 0.9em;
@@ -439,6 +1356,7 @@ async fn generate_directory_listing(
         <div class="header">
             <h1>🌐 HTTP 文件服务器</h1>
         </div>
+        {}
         <div class="path">
             <strong>当前路径:</strong> /{}
         </div>
@@ -463,9 +1381,19 @@ async fn generate_directory_listing(
     <script>
         document.addEventListener('DOMContentLoaded', function() {{
             const fileInput = document.getElementById('fileInput');
+            const folderInput = document.getElementById('folderInput');
             const fileList = document.getElementById('fileList');
             const clearButton = document.getElementById('clearButton');
             const uploadForm = document.getElementById('uploadForm');
+
+            // 只读模式下页面没有上传表单
+            if (!uploadForm) return;
+
+            const uploadBox = uploadForm.querySelector('.upload-box');
+            const uploadButton = uploadForm.querySelector('.upload-button');
+
+            // 待上传的文件及其相对路径；从文件夹中选择或拖入的文件带有子目录
+            let entries = [];
             
             // 格式化文件大小
             function formatFileSize(bytes) {{
@@ -475,38 +1403,41 @@ async fn generate_directory_listing(
                 const i = Math.floor(Math.log(bytes) / Math.log(k));
                 return parseFloat((bytes / Math.pow(k, i)).toFixed(1)) + ' ' + sizes[i];
             }}
+
+            function showMessage(text) {{
+                fileList.innerHTML = '';
+                const message = document.createElement('div');
+                message.className = 'no-files';
+                message.textContent = text;
+                fileList.appendChild(message);
+            }}
             
             // 更新文件列表
             function updateFileList() {{
-                fileList.innerHTML = '';
-                
-                if (fileInput.files.length === 0) {{
+                if (entries.length === 0) {{
                     clearButton.style.display = 'none';
-                    const noFiles = document.createElement('div');
-                    noFiles.className = 'no-files';
-                    noFiles.textContent = '未选中文件';
-                    fileList.appendChild(noFiles);
+                    showMessage('未选中文件');
                     return;
                 }}
                 
+                fileList.innerHTML = '';
                 clearButton.style.display = 'inline-block';
                 
                 // 创建一个文档片段来提高性能
                 const fragment = document.createDocumentFragment();
                 
-                for (let i = 0; i < fileInput.files.length; i++) {{
-                    const file = fileInput.files[i];
+                entries.forEach(function(entry, i) {{
                     const fileItem = document.createElement('div');
                     fileItem.className = 'file-item';
-                    fileItem.dataset.index = i;
                     
                     const fileName = document.createElement('div');
                     fileName.className = 'file-item-name';
-                    fileName.textContent = file.name;
+                    fileName.textContent = entry.path;
+                    fileName.title = entry.path;
                     
                     const fileSize = document.createElement('div');
                     fileSize.className = 'file-item-size';
-                    fileSize.textContent = formatFileSize(file.size);
+                    fileSize.textContent = formatFileSize(entry.file.size);
                     
                     const removeButton = document.createElement('button');
                     removeButton.className = 'remove-file';
@@ -514,57 +1445,240 @@ async fn generate_directory_listing(
                     removeButton.type = 'button';
                     removeButton.title = '移除文件';
                     removeButton.addEventListener('click', function() {{
-                        removeFile(i);
+                        entries.splice(i, 1);
+                        updateFileList();
                     }});
                     
                     fileItem.appendChild(fileName);
                     fileItem.appendChild(fileSize);
                     fileItem.appendChild(removeButton);
                     fragment.appendChild(fileItem);
-                }}
+                }});
                 
                 fileList.appendChild(fragment);
             }}
-            
-            // 移除单个文件
-            function removeFile(index) {{
-                const dt = new DataTransfer();
-                const files = fileInput.files;
-                
-                for (let i = 0; i < files.length; i++) {{
-                    if (i !== index) {{
-                        dt.items.add(files[i]);
-                    }}
+
+            function addFiles(files, pathOf) {{
+                for (const file of files) {{
+                    entries.push({{ file: file, path: pathOf(file) }});
                 }}
-                
-                fileInput.files = dt.files;
                 updateFileList();
             }}
             
-            // 监听文件选择变化
+            // 监听文件和文件夹的选择
             fileInput.addEventListener('change', function() {{
-                updateFileList();
+                addFiles(fileInput.files, function(file) {{ return file.name; }});
+                fileInput.value = '';
+            }});
+            folderInput.addEventListener('change', function() {{
+                addFiles(folderInput.files, function(file) {{ return file.webkitRelativePath || file.name; }});
+                folderInput.value = '';
             }});
             
             // 清除所有文件
             clearButton.addEventListener('click', function() {{
-                fileInput.value = '';
+                entries = [];
                 updateFileList();
             }});
+
+            // 读取拖入的文件或文件夹；文件夹的内容要分批读取，直到返回空列表
+            function readEntry(entry) {{
+                if (entry.isFile) {{
+                    return new Promise(function(resolve, reject) {{
+                        entry.file(function(file) {{
+                            resolve([{{ file: file, path: entry.fullPath.replace(/^\/+/, '') }}]);
+                        }}, reject);
+                    }});
+                }}
+                if (!entry.isDirectory) return Promise.resolve([]);
+                const reader = entry.createReader();
+                const children = [];
+                return new Promise(function(resolve, reject) {{
+                    function readBatch() {{
+                        reader.readEntries(function(batch) {{
+                            if (batch.length > 0) {{
+                                children.push(...batch);
+                                readBatch();
+                            }} else {{
+                                Promise.all(children.map(readEntry)).then(function(lists) {{
+                                    resolve(lists.flat());
+                                }}, reject);
+                            }}
+                        }}, reject);
+                    }}
+                    readBatch();
+                }});
+            }}
+
+            ['dragenter', 'dragover'].forEach(function(type) {{
+                uploadBox.addEventListener(type, function(event) {{
+                    event.preventDefault();
+                    uploadBox.classList.add('dragging');
+                }});
+            }});
+            ['dragleave', 'drop'].forEach(function(type) {{
+                uploadBox.addEventListener(type, function() {{
+                    uploadBox.classList.remove('dragging');
+                }});
+            }});
+            uploadBox.addEventListener('drop', function(event) {{
+                event.preventDefault();
+                const items = Array.from(event.dataTransfer.items || [])
+                    .map(function(item) {{ return item.webkitGetAsEntry ? item.webkitGetAsEntry() : null; }})
+                    .filter(Boolean);
+                if (items.length === 0) {{
+                    addFiles(event.dataTransfer.files, function(file) {{ return file.name; }});
+                    return;
+                }}
+                Promise.all(items.map(readEntry)).then(function(lists) {{
+                    entries.push(...lists.flat());
+                    updateFileList();
+                }}, function() {{
+                    showMessage('无法读取拖入的文件');
+                }});
+            }});
+
+            // 显示每个文件的上传结果
+            function showReport(report) {{
+                const labels = {{
+                    saved: '已保存',
+                    overwritten: '已覆盖同名文件',
+                    skipped: '已有同名文件，已跳过',
+                    conflict: '已有同名文件'
+                }};
+                fileList.innerHTML = '';
+                const summary = document.createElement('div');
+                summary.className = 'no-files';
+                summary.textContent = report.total + ' 个文件中的 ' + report.saved + ' 个上传成功';
+                fileList.appendChild(summary);
+                for (const file of report.files) {{
+                    const fileItem = document.createElement('div');
+                    fileItem.className = 'file-item result';
+                    const fileName = document.createElement('div');
+                    fileName.className = 'file-item-name';
+                    fileName.textContent = file.name;
+                    fileName.title = file.name;
+                    const status = document.createElement('div');
+                    status.className = 'file-item-status ' + (file.saved_as ? 'saved' : file.status === 'skipped' ? 'skipped' : 'failed');
+                    if (file.status === 'renamed') {{
+                        status.textContent = '已有同名文件，保存为 ' + file.saved_as;
+                    }} else {{
+                        status.textContent = file.reason || labels[file.status] || file.status;
+                    }}
+                    fileItem.appendChild(fileName);
+                    fileItem.appendChild(status);
+                    fileList.appendChild(fileItem);
+                }}
+            }}
+
+            // 逐个文件附上相对路径提交；全部按原名保存后刷新目录，否则显示每个文件的结果
+            uploadForm.addEventListener('submit', function(event) {{
+                event.preventDefault();
+                if (entries.length === 0) return;
+
+                // 表单中的其他字段（CSRF 令牌、同名文件处理方式、当前路径）要放在文件之前
+                const data = new FormData();
+                for (const field of uploadForm.elements) {{
+                    if (field.name && field.type !== 'file') {{
+                        data.append(field.name, field.value);
+                    }}
+                }}
+                for (const entry of entries) {{
+                    data.append('relative_path', entry.path);
+                    data.append('file', entry.file, entry.file.name);
+                }}
+
+                uploadButton.disabled = true;
+                uploadButton.textContent = '上传中…';
+                fetch(uploadForm.action, {{
+                    method: 'POST',
+                    body: data,
+                    headers: {{ 'Accept': 'application/json' }}
+                }}).then(function(response) {{
+                    const type = response.headers.get('content-type') || '';
+                    if (type.includes('application/json')) return response.json();
+                    return response.text().then(function(text) {{
+                        throw new Error(text || response.statusText);
+                    }});
+                }}).then(function(report) {{
+                    entries = [];
+                    clearButton.style.display = 'none';
+                    const plain = report.files.every(function(file) {{
+                        return file.status === 'saved' || file.status === 'overwritten';
+                    }});
+                    if (plain) {{
+                        location.reload();
+                    }} else {{
+                        showReport(report);
+                    }}
+                }}).catch(function(error) {{
+                    showMessage('上传失败：' + error.message);
+                }}).finally(function() {{
+                    uploadButton.disabled = false;
+                    uploadButton.textContent = '上传';
+                }});
+            }});
         }});
     </script>
 </body>
 </html>
-    "#, title, current_path, parent_link, file_rows, upload_form))
+    "#, title, account_bar, html_escape(current_path), parent_link, file_rows, upload_form);
+
+    Ok(DirectoryListing { html, validators })
 }
 
-// 提供文件下载服务
-async fn serve_file(file_path: &StdPath) -> Result<Response, Box<dyn std::error::Error>> {
-    let contents = fs::read(file_path).await?;
+// 提供文件下载服务，支持条件请求、预压缩文件、动态压缩和 Range / If-Range 断点续传，文件内容从磁盘流式读取
+async fn serve_file(
+    file_path: &StdPath,
+    req_headers: &HeaderMap,
+    compression: &CompressionConfig,
+) -> Result<Response, Box<dyn std::error::Error>> {
+    let metadata = fs::metadata(file_path).await?;
+    let total = metadata.len();
     let content_type = guess_content_type(file_path);
+    let mut validators = Validators::for_file(&metadata);
+
+    // If-Range 不匹配时忽略 Range，返回完整文件
+    let range_request = match req_headers.get(header::RANGE).and_then(|v| v.to_str().ok()) {
+        Some(value) if range::if_range_allows(req_headers, &validators) => {
+            range::parse_range_header(value, total)
+        }
+        _ => RangeRequest::Full,
+    };
+
+    // 只对完整响应使用压缩版本，Range 始终针对未压缩的原始内容；
+    // 优先使用磁盘上的预压缩文件，没有时再考虑动态压缩
+    let variants = compression::precompressed_variants(file_path, &metadata).await;
+    let (precompressed, encoding) = match range_request {
+        RangeRequest::Full => match compression::select_precompressed(req_headers, &variants) {
+            Some(sidecar) => (Some(sidecar), None),
+            None => (None, compression.negotiate(req_headers, content_type, total)),
+        },
+        _ => (None, None),
+    };
+    if let Some(sidecar) = precompressed {
+        validators = Validators::for_file(&sidecar.metadata);
+        validators.etag = validators.etag.map(|etag| sidecar.encoding.etag(&etag));
+    } else if let Some(encoding) = encoding {
+        validators.etag = validators.etag.map(|etag| encoding.etag(&etag));
+    }
     
     let mut headers = HeaderMap::new();
+    validators.apply(&mut headers);
+    if !variants.is_empty() || compression.varies(content_type) {
+        compression::add_vary(&mut headers);
+    }
+    match conditional::evaluate(req_headers, &validators) {
+        Precondition::Proceed => {}
+        Precondition::NotModified => return Ok((StatusCode::NOT_MODIFIED, headers).into_response()),
+        Precondition::Failed => return Ok(StatusCode::PRECONDITION_FAILED.into_response()),
+    }
+
     headers.insert(header::CONTENT_TYPE, content_type.parse().unwrap());
+    // 动态压缩的响应长度事先未知，无法按字节范围续传，不声明支持 Range
+    if encoding.is_none() {
+        headers.insert(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    }
     
     // 添加文件名到Content-Disposition头
     if let Some(filename) = file_path.file_name() {
@@ -574,8 +1688,56 @@ async fn serve_file(file_path: &StdPath) -> Result<Response, Box<dyn std::error:
         );
         headers.insert(header::CONTENT_DISPOSITION, disposition.parse().unwrap());
     }
-    
-    Ok((headers, contents).into_response())
+
+    match range_request {
+        RangeRequest::Full => {
+            let body = match (precompressed, encoding) {
+                (Some(sidecar), _) => {
+                    let len = sidecar.metadata.len();
+                    sidecar.encoding.apply(&mut headers);
+                    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+                    file_stream::file_body(&sidecar.path, 0, len).await?
+                }
+                (None, Some(encoding)) => {
+                    encoding.apply(&mut headers);
+                    encoding.encode_file(file_path).await?
+                }
+                (None, None) => {
+                    headers.insert(header::CONTENT_LENGTH, HeaderValue::from(total));
+                    file_stream::file_body(file_path, 0, total).await?
+                }
+            };
+            Ok((headers, body).into_response())
+        }
+        RangeRequest::Unsatisfiable => {
+            headers.insert(
+                header::CONTENT_RANGE,
+                format!("bytes */{}", total).parse().unwrap(),
+            );
+            Ok((StatusCode::RANGE_NOT_SATISFIABLE, headers).into_response())
+        }
+        RangeRequest::Partial(ranges) if ranges.len() == 1 => {
+            let range = ranges[0];
+            let body = file_stream::file_body(file_path, range.start, range.len()).await?;
+            headers.insert(
+                header::CONTENT_RANGE,
+                range.content_range(total).parse().unwrap(),
+            );
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(range.len()));
+            Ok((StatusCode::PARTIAL_CONTENT, headers, body).into_response())
+        }
+        RangeRequest::Partial(ranges) => {
+            let boundary = range::multipart_boundary();
+            let (body, length) =
+                range::multipart_body(file_path, &ranges, total, content_type, &boundary).await?;
+            headers.insert(
+                header::CONTENT_TYPE,
+                format!("multipart/byteranges; boundary={}", boundary).parse().unwrap(),
+            );
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(length));
+            Ok((StatusCode::PARTIAL_CONTENT, headers, body).into_response())
+        }
+    }
 }
 
 // 格式化文件大小
@@ -599,23 +1761,30 @@ fn format_file_size(size: u64) -> String {
 // 处理文件上传
 #[axum::debug_handler]
 async fn upload_handler(
-    axum::extract::State(base_dir): axum::extract::State<PathBuf>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    identity: Option<axum::Extension<PeerIdentity>>,
+    caller: Caller,
+    Query(query): Query<UploadQuery>,
+    csrf: CsrfToken,
+    req_headers: HeaderMap,
     mut multipart: Multipart,
 ) -> impl IntoResponse {
-    let mut current_path = String::new();
-    let mut success_count = 0;
-    let mut total_files = 0;
+    let base_dir = &state.base_dir;
+    if state.read_only {
+        return (StatusCode::FORBIDDEN, "服务器处于只读模式，已禁用上传").into_response();
+    }
 
-    // 首先获取当前路径
-    while let Ok(Some(field)) = multipart.next_field().await {
-        let name = field.name().unwrap_or_default().to_string();
-        if name == "current_path" {
-            if let Ok(data) = field.text().await {
-                current_path = data;
-                break;
-            }
-        }
+    // 首先获取当前路径、CSRF 令牌和同名文件的处理方式，表单中这些字段位于文件之前
+    let fields = match read_form_fields(&mut multipart, "current_path").await {
+        Ok(fields) => fields,
+        Err(response) => return response,
+    };
+    if !csrf.verify(fields.get(csrf::CSRF_FIELD).map(String::as_str)) {
+        return csrf::rejected();
     }
+    // 先规范化，去掉 .. 之后不会离开基础目录
+    let current_path = fields.get("current_path").map(|path| mtls::normalize_path(path)).unwrap_or_default();
+    let current_path = current_path.trim_start_matches('/').to_string();
 
     // 确定目标目录
     let target_dir = if current_path.is_empty() {
@@ -625,68 +1794,144 @@ async fn upload_handler(
     };
 
     // 安全检查：确保目标目录在基础目录内
-    if !target_dir.starts_with(&base_dir) {
+    if !upload::is_within(base_dir, &target_dir) {
         return (StatusCode::FORBIDDEN, "访问被拒绝").into_response();
     }
 
-    // 处理所有文件
-    while let Ok(Some(field)) = multipart.next_field().await {
-        let name = field.name().unwrap_or_default().to_string();
-        
-        if name == "file" {
-            if let Some(file_name) = field.file_name() {
-                total_files += 1;
-                let file_name = file_name.to_string();
-                
-                if let Ok(data) = field.bytes().await {
-                    let file_path = target_dir.join(&file_name);
-                    
-                    // 写入文件
-                    match std::fs::File::create(&file_path) {
-                        Ok(mut file) => {
-                            if file.write_all(&data).is_ok() {
-                                success_count += 1;
-                            }
-                        },
-                        Err(_) => {}
-                    }
-                }
-            }
-        }
+    // 上传目标在表单里而不在 URL 中，需要单独按客户端证书规则检查
+    if !state.client_rules.allows(identity.as_ref().map(|e| &e.0), &current_path) {
+        return mtls::forbidden();
+    }
+
+    // 上传需要目标目录的上传权限
+    let url_path = format!("/{}", current_path);
+    if !state.permits(&caller, &url_path, Permission::Upload) {
+        return state.denied(&caller, &req_headers, &url_path);
     }
+    if !target_dir.is_dir() {
+        return (StatusCode::NOT_FOUND, "目标目录不存在").into_response();
+    }
+
+    let on_conflict = match conflict_policy(&fields, &query, state.upload.on_conflict) {
+        Ok(on_conflict) => on_conflict,
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+
+    // 处理所有文件；上传到子目录需要子目录的上传权限（包括客户端证书规则），覆盖已有文件需要删除权限
+    let options = SaveOptions {
+        max_file_size: state.upload.max_file_size,
+        fsync: state.upload.fsync,
+        on_conflict,
+        conflict_field: true,
+        folders: true,
+        private: &state.private,
+    };
+    let saved = upload::save_files(
+        &state.uploads,
+        &mut multipart,
+        &target_dir,
+        options,
+        |relative_path, permission| {
+            let entry_path = format!("{}/{}", url_path.trim_end_matches('/'), relative_path);
+            state.client_rules.allows(identity.as_ref().map(|e| &e.0), &entry_path)
+                && state.permits(&caller, &entry_path, permission)
+        },
+        |_, _| async { Ok(()) },
+    )
+    .await;
+    let report = match saved {
+        Ok(report) => report,
+        Err(e) => return e.into_response(),
+    };
 
-    // 上传后重定向回原目录
+    // 上传后重定向回原目录；有文件被拒绝时显示每个文件的结果
     let redirect_path = if current_path.is_empty() {
         "/".to_string()
     } else {
-        format!("/{}", current_path)
+        format!("/{}", encode_path(&current_path))
     };
+    upload_response(&report, &req_headers, &redirect_path, redirect_path.clone())
+}
 
-    if success_count > 0 {
-        let message = if success_count == total_files {
-            if total_files == 1 {
-                "文件上传成功".to_string()
-            } else {
-                format!("所有{}个文件上传成功", total_files)
-            }
-        } else {
-            format!("{}个文件中的{}个上传成功", total_files, success_count)
-        };
-        
-        (
-            StatusCode::SEE_OTHER,
-            [(header::LOCATION, redirect_path)],
-            message
-        ).into_response()
+// 删除文件或空目录，需要删除权限；浏览器发出的请求要在 X-CSRF-Token 请求头中带上令牌
+async fn delete_handler(
+    Path(path): Path<String>,
+    axum::extract::State(state): axum::extract::State<Arc<AppState>>,
+    caller: Caller,
+    csrf: CsrfToken,
+    req_headers: HeaderMap,
+) -> impl IntoResponse {
+    let base_dir = &state.base_dir;
+    if state.read_only {
+        return (StatusCode::FORBIDDEN, "服务器处于只读模式，已禁用删除").into_response();
+    }
+    if !csrf.verify(None) {
+        return csrf::rejected();
+    }
+
+    // 先规范化，去掉 .. 之后不会离开基础目录；基础目录本身不能删除
+    let url_path = mtls::normalize_path(&path);
+    let relative = url_path.trim_start_matches('/');
+    let target = base_dir.join(relative);
+    if relative.is_empty() || !upload::is_within(base_dir, &target) {
+        return (StatusCode::FORBIDDEN, "访问被拒绝").into_response();
+    }
+
+    if !state.permits(&caller, &url_path, Permission::Delete) {
+        return state.denied(&caller, &req_headers, &url_path);
+    }
+
+    // 符号链接只删除链接本身；途经的目录链接可能指向基础目录之外，这时当作不存在处理
+    let Ok(metadata) = fs::symlink_metadata(&target).await else {
+        return (StatusCode::NOT_FOUND, "文件或目录不存在").into_response();
+    };
+    let inside = match (target.parent(), fs::canonicalize(base_dir).await) {
+        (Some(parent), Ok(base)) => fs::canonicalize(parent).await.is_ok_and(|parent| parent.starts_with(base)),
+        _ => false,
+    };
+    if !inside || state.is_hidden(&target) {
+        return (StatusCode::NOT_FOUND, "文件或目录不存在").into_response();
+    }
+
+    let removed = if metadata.is_dir() {
+        fs::remove_dir(&target).await
     } else {
-        (
-            StatusCode::SEE_OTHER,
-            [(header::LOCATION, redirect_path)],
-            "文件上传失败"
-        ).into_response()
+        fs::remove_file(&target).await
+    };
+    match removed {
+        Ok(()) => {
+            tracing::info!("已删除 {}", url_path);
+            StatusCode::NO_CONTENT.into_response()
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::DirectoryNotEmpty => {
+            (StatusCode::CONFLICT, "目录不为空").into_response()
+        }
+        Err(e) => {
+            tracing::warn!("无法删除 {}: {}", target.display(), e);
+            (StatusCode::INTERNAL_SERVER_ERROR, "无法删除").into_response()
+        }
     }
 }
 
+// 读取文件之前的普通字段，读到 until 字段为止。遇到文件字段就停下，跳过它会丢掉其中的文件；
+// 这时还没有读到 until 字段，说明它在文件之后或者缺少，返回 400
+async fn read_form_fields(multipart: &mut Multipart, until: &str) -> Result<HashMap<String, String>, Response> {
+    let mut fields = HashMap::new();
+    while let Some(field) = multipart.next_field().await.map_err(|e| UploadError::Multipart(e).into_response())? {
+        if field.file_name().is_some() {
+            break;
+        }
+        let name = field.name().unwrap_or_default().to_string();
+        let data = field.text().await.map_err(|e| UploadError::Multipart(e).into_response())?;
+        let done = name == until;
+        fields.insert(name, data);
+        if done {
+            return Ok(fields);
+        }
+    }
+    Err((StatusCode::BAD_REQUEST, format!("表单缺少 {} 字段，它必须位于所有文件之前", until)).into_response())
+}
+
 // 根据文件扩展名猜测MIME类型
 fn guess_content_type(file_path: &StdPath) -> &'static str {
     match file_path.extension().and_then(|ext| ext.to_str()) {
@@ -712,3 +1957,79 @@ fn guess_content_type(file_path: &StdPath) -> &'static str {
         _ => "application/octet-stream",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_path_escapes_each_segment() {
+        assert_eq!(encode_path("docs/a b.txt"), "docs/a%20b.txt");
+        assert_eq!(
+            encode_path("x' onmouseover='alert(1)' a='.txt"),
+            "x%27%20onmouseover%3D%27alert%281%29%27%20a%3D%27.txt"
+        );
+        assert_eq!(encode_path("<b>&/\"q\""), "%3Cb%3E%26/%22q%22");
+        assert_eq!(encode_path("中文/-._~"), "%E4%B8%AD%E6%96%87/-._~");
+    }
+
+    #[tokio::test]
+    async fn listing_escapes_hostile_names() {
+        let dir = std::env::temp_dir().join(format!("myhs-listing-{}", tokens::random_hex(4)));
+        let hostile_dir = "<img src=x onerror=alert(1)>&";
+        let hostile_file = "x' onmouseover='alert(document.body.innerHTML)' a='.txt";
+        std::fs::create_dir_all(dir.join(hostile_dir)).unwrap();
+        std::fs::write(dir.join(hostile_file), "x").unwrap();
+
+        let listing = generate_directory_listing(
+            &dir,
+            "a'b<c>",
+            "",
+            Some(ConflictPolicy::Overwrite),
+            None,
+            &PrivateFiles::default(),
+            &CsrfToken::default(),
+        )
+        .await
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let html = listing.html;
+        assert!(!html.contains(hostile_file));
+        assert!(!html.contains(hostile_dir));
+        assert!(!html.contains("a'b<c>"));
+        assert!(html.contains("x&#39; onmouseover=&#39;alert(document.body.innerHTML)&#39; a=&#39;.txt"));
+        assert!(html.contains("href='/a%27b%3Cc%3E/x%27%20onmouseover"));
+        assert!(html.contains("&lt;img src=x onerror=alert(1)&gt;&amp;/"));
+        assert!(html.contains(r#"name="current_path" value="a&#39;b&lt;c&gt;""#));
+    }
+
+    // 上传文件夹时目录名由客户端决定，同样要在列表中转义
+    #[tokio::test]
+    async fn listing_escapes_uploaded_folder_names() {
+        let (dirs, file_name) = upload::sanitize_relative_path("a' onclick='x()/b' onmouseover='y()&amp;/f.txt").unwrap();
+        assert_eq!(dirs, ["a' onclick='x()", "b' onmouseover='y()&amp;"]);
+        assert_eq!(file_name, "f.txt");
+
+        let root = std::env::temp_dir().join(format!("myhs-folder-{}", tokens::random_hex(4)));
+        std::fs::create_dir_all(root.join(&dirs[0]).join(&dirs[1])).unwrap();
+        let listing = generate_directory_listing(
+            &root.join(&dirs[0]),
+            &dirs[0],
+            "",
+            None,
+            None,
+            &PrivateFiles::default(),
+            &CsrfToken::default(),
+        )
+        .await
+        .unwrap();
+        std::fs::remove_dir_all(&root).unwrap();
+
+        let html = listing.html;
+        assert!(!html.contains("onclick='"));
+        assert!(!html.contains("onmouseover='"));
+        assert!(html.contains("href='/a%27%20onclick%3D%27x%28%29/b%27%20onmouseover%3D%27y%28%29%26amp%3B'"));
+        assert!(html.contains("b&#39; onmouseover=&#39;y()&amp;amp;/"));
+    }
+}
@@ -0,0 +1,234 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use std::{fmt, net::IpAddr, str::FromStr, sync::Arc};
+use x509_parser::{extensions::GeneralName, prelude::FromDer};
+
+// 通过客户端证书认证的对端身份，作为请求扩展传给中间件和处理器
+#[derive(Debug, Clone)]
+pub struct PeerIdentity {
+    // 完整的证书主题，例如 CN=ci-runner, O=Example
+    pub subject: String,
+    pub common_name: Option<String>,
+    pub dns_names: Vec<String>,
+    pub emails: Vec<String>,
+    pub uris: Vec<String>,
+    pub ips: Vec<IpAddr>,
+}
+
+impl PeerIdentity {
+    // 从客户端的叶子证书中提取身份；证书已由 TLS 层校验过
+    pub fn from_der(der: &[u8]) -> Option<PeerIdentity> {
+        let (_, cert) = x509_parser::certificate::X509Certificate::from_der(der).ok()?;
+        let subject = cert.subject();
+        let mut identity = PeerIdentity {
+            subject: subject.to_string(),
+            common_name: subject
+                .iter_common_name()
+                .next()
+                .and_then(|cn| cn.as_str().ok())
+                .map(String::from),
+            dns_names: Vec::new(),
+            emails: Vec::new(),
+            uris: Vec::new(),
+            ips: Vec::new(),
+        };
+
+        if let Ok(Some(san)) = cert.subject_alternative_name() {
+            for name in &san.value.general_names {
+                match name {
+                    GeneralName::DNSName(dns) => identity.dns_names.push(dns.to_ascii_lowercase()),
+                    GeneralName::RFC822Name(email) => identity.emails.push(email.to_string()),
+                    GeneralName::URI(uri) => identity.uris.push(uri.to_string()),
+                    GeneralName::IPAddress(bytes) => {
+                        let ip = match bytes.len() {
+                            4 => <[u8; 4]>::try_from(*bytes).ok().map(IpAddr::from),
+                            16 => <[u8; 16]>::try_from(*bytes).ok().map(IpAddr::from),
+                            _ => None,
+                        };
+                        identity.ips.extend(ip);
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Some(identity)
+    }
+}
+
+impl fmt::Display for PeerIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.common_name {
+            Some(cn) => write!(f, "CN={}", cn),
+            None => write!(f, "{}", self.subject),
+        }
+    }
+}
+
+// 访问规则中的一个身份：CN:名称、DNS:域名、EMAIL:邮箱、URI:地址 或 IP:地址
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum IdentityMatcher {
+    CommonName(String),
+    Dns(String),
+    Email(String),
+    Uri(String),
+    Ip(IpAddr),
+}
+
+impl IdentityMatcher {
+    fn matches(&self, identity: &PeerIdentity) -> bool {
+        match self {
+            IdentityMatcher::CommonName(cn) => identity.common_name.as_deref() == Some(cn.as_str()),
+            IdentityMatcher::Dns(dns) => identity.dns_names.iter().any(|name| name == dns),
+            IdentityMatcher::Email(email) => identity.emails.iter().any(|e| e.eq_ignore_ascii_case(email)),
+            IdentityMatcher::Uri(uri) => identity.uris.iter().any(|u| u == uri),
+            IdentityMatcher::Ip(ip) => identity.ips.contains(ip),
+        }
+    }
+}
+
+impl FromStr for IdentityMatcher {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || {
+            format!(
+                "'{}' 不是有效的证书身份，应为 CN:名称、DNS:域名、EMAIL:邮箱、URI:地址 或 IP:地址",
+                value
+            )
+        };
+        let (kind, name) = value.trim().split_once(':').ok_or_else(invalid)?;
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        match kind.trim().to_ascii_uppercase().as_str() {
+            "CN" => Ok(IdentityMatcher::CommonName(name.to_string())),
+            "DNS" => Ok(IdentityMatcher::Dns(name.to_ascii_lowercase())),
+            "EMAIL" => Ok(IdentityMatcher::Email(name.to_string())),
+            "URI" => Ok(IdentityMatcher::Uri(name.to_string())),
+            "IP" => name.parse().map(IdentityMatcher::Ip).map_err(|_| invalid()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl TryFrom<String> for IdentityMatcher {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+// 路径访问规则：该路径及其子路径只允许列出的证书身份访问
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientRule {
+    #[serde(deserialize_with = "deserialize_rule_path")]
+    pub path: String,
+    pub allow: Vec<IdentityMatcher>,
+}
+
+// 命令行写法：路径=身份,身份，例如 /releases=CN:ci-runner,DNS:build01.internal
+impl FromStr for ClientRule {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (path, allow) = value
+            .split_once('=')
+            .ok_or_else(|| format!("'{}' 不是有效的访问规则，应为 路径=身份,身份，例如 /releases=CN:ci-runner", value))?;
+        let allow = allow
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<IdentityMatcher>, _>>()?;
+        if allow.is_empty() {
+            return Err(format!("访问规则 '{}' 没有列出任何证书身份", value));
+        }
+        Ok(ClientRule {
+            path: normalize_path(path),
+            allow,
+        })
+    }
+}
+
+fn deserialize_rule_path<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    String::deserialize(deserializer).map(|path| normalize_path(&path))
+}
+
+// 按路径授权的规则集合；没有规则覆盖的路径对所有持有有效证书的客户端开放
+#[derive(Debug, Clone, Default)]
+pub struct ClientRules {
+    rules: Vec<ClientRule>,
+}
+
+impl ClientRules {
+    pub fn new(rules: Vec<ClientRule>) -> Self {
+        ClientRules { rules }
+    }
+
+    // 由最长的匹配路径决定是否允许访问
+    pub fn allows(&self, identity: Option<&PeerIdentity>, path: &str) -> bool {
+        let path = normalize_path(path);
+        let rule = self
+            .rules
+            .iter()
+            .filter(|rule| path_within(&path, &rule.path))
+            .max_by_key(|rule| rule.path.len());
+        match rule {
+            None => true,
+            Some(rule) => identity.is_some_and(|identity| rule.allow.iter().any(|m| m.matches(identity))),
+        }
+    }
+}
+
+// 按请求路径检查客户端证书身份的中间件
+pub async fn authorize(State(rules): State<Arc<ClientRules>>, request: Request, next: Next) -> Response {
+    let path = percent_encoding::percent_decode_str(request.uri().path()).decode_utf8_lossy();
+    let identity = request.extensions().get::<PeerIdentity>();
+    if !rules.allows(identity, &path) {
+        tracing::info!(
+            "拒绝 {} 访问 {}",
+            identity.map(|i| i.to_string()).unwrap_or_else(|| "未知客户端".to_string()),
+            path
+        );
+        return forbidden();
+    }
+    next.run(request).await
+}
+
+pub fn forbidden() -> Response {
+    (StatusCode::FORBIDDEN, "客户端证书无权访问该路径").into_response()
+}
+
+// 规范化为以 / 开头、不含 . 和 .. 的路径，避免用 /public/../private 绕过规则
+pub fn normalize_path(path: &str) -> String {
+    let mut segments: Vec<&str> = Vec::new();
+    for segment in path.split(['/', '\\']) {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop();
+            }
+            segment => segments.push(segment),
+        }
+    }
+    format!("/{}", segments.join("/"))
+}
+
+// path 是否等于 prefix 或位于其下（按路径段比较）
+pub fn path_within(path: &str, prefix: &str) -> bool {
+    prefix == "/"
+        || path == prefix
+        || path.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+}
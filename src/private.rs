@@ -0,0 +1,55 @@
+use std::path::{Path as StdPath, PathBuf};
+
+// 服务目录中不对外提供的文件：加载的配置文件，以及其中引用的账号、令牌、分享链接、密码文件和 TLS 私钥。
+// 默认的服务目录就是放配置文件的当前目录，这些文件不能被列出、下载，也不能被上传的文件覆盖或抢先创建
+#[derive(Debug, Default)]
+pub struct PrivateFiles {
+    paths: Vec<PathBuf>,
+}
+
+impl PrivateFiles {
+    pub fn new<'a>(files: impl IntoIterator<Item = &'a PathBuf>) -> PrivateFiles {
+        let mut paths: Vec<PathBuf> = files.into_iter().filter_map(|file| canonical(file)).collect();
+        paths.sort();
+        paths.dedup();
+        PrivateFiles { paths }
+    }
+
+    // 按规范化后的路径比较，经过符号链接或 .. 也能识别
+    pub fn contains(&self, path: &StdPath) -> bool {
+        !self.paths.is_empty() && canonical(path).is_some_and(|path| self.paths.contains(&path))
+    }
+}
+
+// 文件可能还不存在（例如尚未创建的令牌文件），此时规范化所在的目录再接上文件名
+fn canonical(path: &StdPath) -> Option<PathBuf> {
+    if let Ok(path) = path.canonicalize() {
+        return Some(path);
+    }
+    let name = path.file_name()?;
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(StdPath::new("."));
+    parent.canonicalize().ok().map(|parent| parent.join(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_existing_and_missing_files() {
+        let dir = std::env::temp_dir().join(format!("myhs-private-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("sub")).unwrap();
+        let config = dir.join("myhs.toml");
+        std::fs::write(&config, "").unwrap();
+        let tokens = dir.join("tokens.toml");
+        let private = PrivateFiles::new([&config, &tokens]);
+
+        assert!(private.contains(&config));
+        assert!(private.contains(&dir.join("sub/../myhs.toml")));
+        // 还不存在的文件同样不能被创建
+        assert!(private.contains(&tokens));
+        assert!(!private.contains(&dir.join("other.toml")));
+        assert!(!PrivateFiles::default().contains(&config));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,249 @@
+use axum::{
+    body::Body,
+    http::{header, HeaderMap},
+};
+use std::{
+    path::Path as StdPath,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{conditional::Validators, file_stream};
+
+// 单个请求允许的最大区间数，超过则忽略 Range 头直接返回完整文件
+const MAX_RANGES: usize = 32;
+
+// 闭区间 [start, end]，与 Content-Range 的表示方式一致
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ByteRange {
+    pub fn len(&self) -> u64 {
+        self.end - self.start + 1
+    }
+
+    pub fn content_range(&self, total: u64) -> String {
+        format!("bytes {}-{}/{}", self.start, self.end, total)
+    }
+}
+
+// Range 头的解析结果
+#[derive(Debug, PartialEq, Eq)]
+pub enum RangeRequest {
+    // 没有 Range 头或格式无法识别，返回完整内容
+    Full,
+    // 至少有一个可满足的区间
+    Partial(Vec<ByteRange>),
+    // 语法正确但没有任何区间落在文件范围内，返回 416
+    Unsatisfiable,
+}
+
+// 解析 `Range: bytes=...`，按 RFC 9110 忽略无法识别的单位和格式错误的头
+pub fn parse_range_header(value: &str, total: u64) -> RangeRequest {
+    let value = value.trim();
+    let Some((unit, specs)) = value.split_once('=') else {
+        return RangeRequest::Full;
+    };
+    if !unit.trim().eq_ignore_ascii_case("bytes") {
+        return RangeRequest::Full;
+    }
+
+    let mut ranges = Vec::new();
+    let mut spec_count = 0;
+    for spec in specs.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        spec_count += 1;
+        if spec_count > MAX_RANGES {
+            return RangeRequest::Full;
+        }
+
+        let Some((first, last)) = spec.split_once('-') else {
+            return RangeRequest::Full;
+        };
+        let (first, last) = (first.trim(), last.trim());
+
+        if first.is_empty() {
+            // 后缀区间 `-N`：最后 N 个字节
+            let Ok(suffix) = last.parse::<u64>() else {
+                return RangeRequest::Full;
+            };
+            if suffix > 0 && total > 0 {
+                ranges.push(ByteRange {
+                    start: total.saturating_sub(suffix),
+                    end: total - 1,
+                });
+            }
+            continue;
+        }
+
+        let Ok(start) = first.parse::<u64>() else {
+            return RangeRequest::Full;
+        };
+        let end = if last.is_empty() {
+            u64::MAX
+        } else {
+            match last.parse::<u64>() {
+                Ok(end) if end >= start => end,
+                _ => return RangeRequest::Full,
+            }
+        };
+        if start < total {
+            ranges.push(ByteRange {
+                start,
+                end: end.min(total - 1),
+            });
+        }
+    }
+
+    if spec_count == 0 {
+        return RangeRequest::Full;
+    }
+    if ranges.is_empty() {
+        return RangeRequest::Unsatisfiable;
+    }
+
+    RangeRequest::Partial(coalesce(ranges))
+}
+
+// 合并重叠或相邻的区间，避免客户端用大量碎片区间放大响应
+fn coalesce(mut ranges: Vec<ByteRange>) -> Vec<ByteRange> {
+    if ranges.len() < 2 {
+        return ranges;
+    }
+    ranges.sort_by_key(|r| r.start);
+
+    let mut merged: Vec<ByteRange> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end.saturating_add(1) => {
+                last.end = last.end.max(range.end);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+// 判断 If-Range 是否允许返回部分内容；不匹配时应返回完整文件
+pub fn if_range_allows(req_headers: &HeaderMap, validators: &Validators) -> bool {
+    match req_headers.get(header::IF_RANGE).and_then(|v| v.to_str().ok()) {
+        Some(value) => validators.if_range_matches(value),
+        None => true,
+    }
+}
+
+// 生成 multipart/byteranges 的分隔符
+pub fn multipart_boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("MYHS_BYTERANGES_{:x}", nanos)
+}
+
+// 组装多区间响应体，返回流式 Body 以及用于 Content-Length 的总长度
+pub async fn multipart_body(
+    file_path: &StdPath,
+    ranges: &[ByteRange],
+    total: u64,
+    content_type: &str,
+    boundary: &str,
+) -> std::io::Result<(Body, u64)> {
+    let mut streams = Vec::with_capacity(ranges.len() * 2 + 1);
+    let mut length = 0;
+    for range in ranges {
+        let part_header = part_header(boundary, content_type, *range, total);
+        length += part_header.len() as u64 + range.len();
+        streams.push(file_stream::bytes_stream(part_header));
+        streams.push(file_stream::open_stream(file_path, range.start, range.len()).await?);
+    }
+    let closing = closing_boundary(boundary);
+    length += closing.len() as u64;
+    streams.push(file_stream::bytes_stream(closing));
+    Ok((file_stream::concat(streams), length))
+}
+
+fn part_header(boundary: &str, content_type: &str, range: ByteRange, total: u64) -> String {
+    format!(
+        "\r\n--{}\r\nContent-Type: {}\r\nContent-Range: {}\r\n\r\n",
+        boundary,
+        content_type,
+        range.content_range(total)
+    )
+}
+
+fn closing_boundary(boundary: &str) -> String {
+    format!("\r\n--{}--\r\n", boundary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: u64, end: u64) -> ByteRange {
+        ByteRange { start, end }
+    }
+
+    #[test]
+    fn parses_single_and_open_ranges() {
+        assert_eq!(parse_range_header("bytes=0-9", 100), RangeRequest::Partial(vec![range(0, 9)]));
+        assert_eq!(parse_range_header("bytes=90-", 100), RangeRequest::Partial(vec![range(90, 99)]));
+        // 结束位置超出文件时截断到最后一个字节
+        assert_eq!(parse_range_header("bytes=50-500", 100), RangeRequest::Partial(vec![range(50, 99)]));
+    }
+
+    #[test]
+    fn parses_suffix_ranges() {
+        assert_eq!(parse_range_header("bytes=-10", 100), RangeRequest::Partial(vec![range(90, 99)]));
+        assert_eq!(parse_range_header("bytes=-500", 100), RangeRequest::Partial(vec![range(0, 99)]));
+        assert_eq!(parse_range_header("bytes=-0", 100), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range_header("bytes=-10", 0), RangeRequest::Unsatisfiable);
+    }
+
+    #[test]
+    fn merges_overlapping_and_adjacent_ranges() {
+        assert_eq!(
+            parse_range_header("bytes=20-29, 0-9, 5-14, 15-16", 100),
+            RangeRequest::Partial(vec![range(0, 16), range(20, 29)])
+        );
+        assert_eq!(
+            parse_range_header("bytes=0-49, -60", 100),
+            RangeRequest::Partial(vec![range(0, 99)])
+        );
+    }
+
+    #[test]
+    fn rejects_unsatisfiable_ranges() {
+        assert_eq!(parse_range_header("bytes=100-", 100), RangeRequest::Unsatisfiable);
+        assert_eq!(parse_range_header("bytes=200-300, 150-", 100), RangeRequest::Unsatisfiable);
+        // 只要有一个区间可满足就返回部分内容
+        assert_eq!(
+            parse_range_header("bytes=200-300, 0-0", 100),
+            RangeRequest::Partial(vec![range(0, 0)])
+        );
+    }
+
+    #[test]
+    fn if_range_mismatch_falls_back_to_full_response() {
+        let validators = Validators {
+            etag: Some("\"abc\"".to_string()),
+            last_modified: None,
+        };
+        let mut headers = HeaderMap::new();
+        assert!(if_range_allows(&headers, &validators));
+        headers.insert(header::IF_RANGE, "\"abc\"".parse().unwrap());
+        assert!(if_range_allows(&headers, &validators));
+        headers.insert(header::IF_RANGE, "\"changed\"".parse().unwrap());
+        assert!(!if_range_allows(&headers, &validators));
+    }
+
+    #[test]
+    fn ignores_malformed_headers() {
+        for value in ["bytes", "items=0-9", "bytes=", "bytes=9-0", "bytes=a-b", "bytes=0-9;x", "bytes=--5"] {
+            assert_eq!(parse_range_header(value, 100), RangeRequest::Full, "{}", value);
+        }
+        let many = format!("bytes={}", vec!["0-0"; MAX_RANGES + 1].join(","));
+        assert_eq!(parse_range_header(&many, 100), RangeRequest::Full);
+    }
+}
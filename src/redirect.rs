@@ -0,0 +1,49 @@
+use axum::{
+    extract::State,
+    http::{header, uri::Authority, HeaderMap, StatusCode, Uri},
+    response::{IntoResponse, Response},
+    Router,
+};
+use tower_http::trace::TraceLayer;
+
+use crate::server;
+
+// 未单独指定端口的跳转监听地址使用的端口
+pub const DEFAULT_REDIRECT_PORT: u16 = 80;
+
+// 只负责跳转的 HTTP 服务：所有请求都用 308 跳转到同一主机的 HTTPS 地址，保留路径和查询参数
+pub fn router(https_port: u16) -> Router {
+    Router::new()
+        .fallback(redirect_handler)
+        .layer(TraceLayer::new_for_http().make_span_with(server::request_span))
+        .with_state(https_port)
+}
+
+async fn redirect_handler(State(https_port): State<u16>, headers: HeaderMap, uri: Uri) -> Response {
+    // HTTP/2 请求没有 Host 头，主机名在 URI 里
+    let authority = uri.authority().cloned().or_else(|| {
+        headers
+            .get(header::HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| host.parse::<Authority>().ok())
+    });
+    let Some(authority) = authority else {
+        return (StatusCode::BAD_REQUEST, "缺少 Host 请求头，无法跳转到 HTTPS").into_response();
+    };
+
+    // host() 对 IPv6 地址保留方括号，可以直接拼接端口
+    let host = authority.host();
+    let path = uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+    let location = if https_port == 443 {
+        format!("https://{}{}", host, path)
+    } else {
+        format!("https://{}:{}{}", host, https_port, path)
+    };
+
+    (
+        StatusCode::PERMANENT_REDIRECT,
+        [(header::LOCATION, location.clone())],
+        format!("请使用 HTTPS 访问: {}\n", location),
+    )
+        .into_response()
+}
@@ -0,0 +1,115 @@
+use axum::{body::Body, extract::Request, response::Response, routing::future::RouteFuture, Router};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo},
+    server::{conn::auto::Builder, graceful::GracefulShutdown},
+};
+use std::{convert::Infallible, time::Duration};
+use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
+use tokio_util::sync::CancellationToken;
+use tower::Service;
+
+use crate::mtls::PeerIdentity;
+
+// TLS 握手的最长时间，避免半开连接一直占用资源
+const TLS_HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// 接受失败（例如文件描述符耗尽）后的退避时间
+const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
+// 在一个监听器上提供服务，HTTP/1.1 和 HTTP/2 自动识别；配置了 tls 时先完成 TLS 握手。
+// stop 触发后不再接受新连接，等已有连接上的请求处理完后返回
+pub async fn serve(listener: TcpListener, app: Router, tls: Option<TlsAcceptor>, stop: CancellationToken) {
+    let graceful = GracefulShutdown::new();
+
+    loop {
+        let (stream, remote_addr) = tokio::select! {
+            result = listener.accept() => match result {
+                Ok(conn) => conn,
+                Err(e) => {
+                    tracing::warn!("接受连接失败: {}", e);
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            },
+            _ = stop.cancelled() => break,
+        };
+        let _ = stream.set_nodelay(true);
+
+        let watcher = graceful.watcher();
+        let app = app.clone();
+        let tls = tls.clone();
+        tokio::spawn(async move {
+            let builder = Builder::new(TokioExecutor::new());
+
+            match tls {
+                Some(acceptor) => {
+                    let stream = match tokio::time::timeout(TLS_HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => stream,
+                        Ok(Err(e)) => {
+                            tracing::debug!("与 {} 的 TLS 握手失败: {}", remote_addr, e);
+                            return;
+                        }
+                        Err(_) => {
+                            tracing::debug!("与 {} 的 TLS 握手超时", remote_addr);
+                            return;
+                        }
+                    };
+                    // 客户端证书已由 TLS 层校验，身份随每个请求传给后续中间件
+                    let identity = stream
+                        .get_ref()
+                        .1
+                        .peer_certificates()
+                        .and_then(|certs| certs.first())
+                        .and_then(|cert| PeerIdentity::from_der(cert));
+                    let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), ConnectionService { app, identity });
+                    let _ = watcher.watch(conn).await;
+                }
+                None => {
+                    let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), ConnectionService { app, identity: None });
+                    let _ = watcher.watch(conn).await;
+                }
+            }
+        });
+    }
+
+    // 先关闭监听套接字，再通知所有连接在当前请求结束后关闭
+    drop(listener);
+    graceful.shutdown().await;
+}
+
+// 单个连接上的服务：把 hyper 的请求交给 Router，并附带连接级的信息
+struct ConnectionService {
+    app: Router,
+    identity: Option<PeerIdentity>,
+}
+
+impl hyper::service::Service<Request<Incoming>> for ConnectionService {
+    type Response = Response;
+    type Error = Infallible;
+    type Future = RouteFuture<Infallible>;
+
+    // Router 总是就绪，无需等待 poll_ready
+    fn call(&self, request: Request<Incoming>) -> Self::Future {
+        let mut request = request.map(Body::new);
+        if let Some(identity) = &self.identity {
+            request.extensions_mut().insert(identity.clone());
+        }
+        self.app.clone().call(request)
+    }
+}
+
+// 请求日志的 span，使用客户端证书认证时附带对端身份；
+// target 与 TraceLayer 默认的 span 相同，原有的日志过滤规则继续生效
+pub fn request_span(request: &Request) -> tracing::Span {
+    let peer = request.extensions().get::<PeerIdentity>();
+    tracing::debug_span!(
+        target: "tower_http::trace::make_span",
+        "request",
+        method = %request.method(),
+        uri = %request.uri(),
+        version = ?request.version(),
+        peer = peer.map(tracing::field::display),
+    )
+}
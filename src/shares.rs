@@ -0,0 +1,682 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine};
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::{
+    path::{Path as StdPath, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{accounts::html_escape, auth, csrf::CsrfToken, mtls, store, tokens};
+
+type ShareResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+const KIND: &str = "分享链接文件";
+
+// 创建时没有指定过期时间时链接的默认有效期
+pub const DEFAULT_SHARE_EXPIRY: &str = "7d";
+
+// 签名截取的字节数，足以防止伪造，同时让链接保持简短
+const SIGNATURE_LEN: usize = 16;
+
+// 分享链接配置
+#[derive(Debug, Clone)]
+pub struct ShareConfig {
+    pub file: PathBuf,
+    // 签名密钥；未配置时使用分享链接文件中自动生成的密钥
+    pub key: Option<String>,
+}
+
+// 分享链接文件的内容
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ShareFile {
+    // 自动生成的签名密钥（十六进制）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    #[serde(default)]
+    links: Vec<ShareLink>,
+    #[serde(default)]
+    uploads: Vec<UploadLink>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ShareLink {
+    id: String,
+    // 相对服务目录的路径，以 / 开头
+    path: String,
+    created: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_downloads: Option<u64>,
+    #[serde(default)]
+    downloads: u64,
+}
+
+impl ShareLink {
+    fn usable(&self, now: u64) -> Result<(), ShareError> {
+        let expired = self.expires.is_some_and(|expires| expires <= now);
+        let exhausted = self.max_downloads.is_some_and(|max| self.downloads >= max);
+        if expired || exhausted {
+            Err(ShareError::Expired)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// 上传链接：可以向一个目录上传文件，但不能查看目录中已有的文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct UploadLink {
+    id: String,
+    // 接收文件的目录，相对服务目录，以 / 开头
+    path: String,
+    created: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_files: Option<u64>,
+    // 所有上传文件的总字节数上限
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max_bytes: Option<u64>,
+    #[serde(default)]
+    files: u64,
+    #[serde(default)]
+    bytes: u64,
+}
+
+impl UploadLink {
+    fn usable(&self, now: u64) -> Result<(), ShareError> {
+        let expired = self.expires.is_some_and(|expires| expires <= now);
+        let exhausted = self.max_files.is_some_and(|max| self.files >= max)
+            || self.max_bytes.is_some_and(|max| self.bytes >= max);
+        if expired || exhausted {
+            Err(ShareError::Expired)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// 带签名的链接；签名覆盖 ID、路径、过期时间和各项上限，修改文件中的任何一项都会使链接失效
+trait Signed {
+    fn id(&self) -> &str;
+    fn message(&self) -> String;
+
+    fn signature(&self, key: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC 接受任意长度的密钥");
+        mac.update(self.message().as_bytes());
+        BASE64_URL.encode(&mac.finalize().into_bytes()[..SIGNATURE_LEN])
+    }
+
+    fn token(&self, key: &[u8]) -> String {
+        format!("{}.{}", self.id(), self.signature(key))
+    }
+}
+
+impl Signed for ShareLink {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn message(&self) -> String {
+        format!(
+            "{}\n{}\n{}\n{}",
+            self.id,
+            self.path,
+            self.expires.unwrap_or(0),
+            self.max_downloads.unwrap_or(0)
+        )
+    }
+}
+
+impl Signed for UploadLink {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    // 以 upload 开头，下载链接的签名不能用于上传
+    fn message(&self) -> String {
+        format!(
+            "upload\n{}\n{}\n{}\n{}\n{}",
+            self.id,
+            self.path,
+            self.expires.unwrap_or(0),
+            self.max_files.unwrap_or(0),
+            self.max_bytes.unwrap_or(0)
+        )
+    }
+}
+
+// 通过校验的分享链接
+#[derive(Debug, Clone)]
+pub struct SharedPath {
+    pub id: String,
+    pub path: String,
+    // 是否限制了下载次数
+    pub limited: bool,
+}
+
+// 通过校验的上传链接及其剩余额度
+#[derive(Debug, Clone)]
+pub struct UploadTarget {
+    pub id: String,
+    pub path: String,
+    pub expires: Option<u64>,
+    pub remaining_files: Option<u64>,
+    pub remaining_bytes: Option<u64>,
+}
+
+// 链接不可用的原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShareError {
+    // 签名不符、不存在或已被吊销
+    Invalid,
+    // 已过期，或下载次数、上传额度已用完
+    Expired,
+    // 文件超出上传链接剩余的容量
+    TooLarge,
+    // 无法锁定、重新读取或写回链接文件，次数和额度没有记录下来
+    Unavailable,
+}
+
+// 创建分享链接时的参数
+#[derive(Debug, Clone)]
+pub struct NewShare {
+    pub path: String,
+    pub expires: Option<u64>,
+    pub max_downloads: Option<u64>,
+}
+
+// 创建上传链接时的参数
+#[derive(Debug, Clone)]
+pub struct NewUploadLink {
+    pub path: String,
+    pub expires: Option<u64>,
+    pub max_files: Option<u64>,
+    pub max_bytes: Option<u64>,
+}
+
+#[derive(Debug)]
+struct Loaded {
+    modified: Option<SystemTime>,
+    file: ShareFile,
+}
+
+// 分享链接文件；由 `MyHS share` 子命令创建和吊销，下载次数由服务器写回
+#[derive(Debug)]
+pub struct ShareStore {
+    path: PathBuf,
+    key: Option<Vec<u8>>,
+    loaded: Mutex<Loaded>,
+}
+
+impl ShareStore {
+    pub fn open(config: &ShareConfig) -> ShareResult<ShareStore> {
+        let file: ShareFile = store::read_toml(&config.file, KIND)?;
+        Ok(ShareStore {
+            path: config.file.clone(),
+            key: config.key.as_ref().map(|key| key.as_bytes().to_vec()),
+            loaded: Mutex::new(Loaded {
+                modified: store::modified(&config.file),
+                file,
+            }),
+        })
+    }
+
+    // 仍然有效的下载链接和上传链接数量
+    pub fn active_counts(&self) -> (usize, usize) {
+        let now = tokens::unix_now();
+        let loaded = self.loaded.lock().unwrap();
+        (
+            loaded.file.links.iter().filter(|link| link.usable(now).is_ok()).count(),
+            loaded.file.uploads.iter().filter(|link| link.usable(now).is_ok()).count(),
+        )
+    }
+
+    // 校验 /s/ 后面的令牌，返回分享的路径
+    pub async fn resolve(self: &Arc<Self>, token: &str) -> Result<SharedPath, ShareError> {
+        let token = token.to_string();
+        self.read(move |file, key| {
+            let link = find(&file.links, key, &token)?;
+            link.usable(tokens::unix_now())?;
+            Ok(SharedPath {
+                id: link.id.clone(),
+                path: link.path.clone(),
+                limited: link.max_downloads.is_some(),
+            })
+        })
+        .await
+    }
+
+    // 记录一次下载；次数已用完时返回错误
+    pub async fn record_download(self: &Arc<Self>, id: &str) -> Result<(), ShareError> {
+        let id = id.to_string();
+        self.update(move |file| {
+            let link = file.links.iter_mut().find(|link| link.id == id).ok_or(ShareError::Invalid)?;
+            link.usable(tokens::unix_now())?;
+            link.downloads += 1;
+            Ok(())
+        })
+        .await
+    }
+
+    // 校验 /u/ 后面的令牌，返回接收文件的目录和剩余额度
+    pub async fn resolve_upload(self: &Arc<Self>, token: &str) -> Result<UploadTarget, ShareError> {
+        let token = token.to_string();
+        self.read(move |file, key| {
+            let link = find(&file.uploads, key, &token)?;
+            link.usable(tokens::unix_now())?;
+            Ok(UploadTarget {
+                id: link.id.clone(),
+                path: link.path.clone(),
+                expires: link.expires,
+                remaining_files: link.max_files.map(|max| max - link.files),
+                remaining_bytes: link.max_bytes.map(|max| max - link.bytes),
+            })
+        })
+        .await
+    }
+
+    // 文件完整收到后按实际大小占用上传额度；额度不足时拒绝该文件
+    pub async fn reserve_upload(self: &Arc<Self>, id: &str, size: u64) -> Result<(), ShareError> {
+        let id = id.to_string();
+        self.update(move |file| {
+            let link = file.uploads.iter_mut().find(|link| link.id == id).ok_or(ShareError::Invalid)?;
+            link.usable(tokens::unix_now())?;
+            if link.max_bytes.is_some_and(|max| link.bytes + size > max) {
+                return Err(ShareError::TooLarge);
+            }
+            link.files += 1;
+            link.bytes += size;
+            Ok(())
+        })
+        .await
+    }
+
+    // 在阻塞线程中读取链接（文件有变化时先重新加载），不占用异步工作线程
+    async fn read<T: Send + 'static>(
+        self: &Arc<Self>,
+        f: impl FnOnce(&ShareFile, &[u8]) -> Result<T, ShareError> + Send + 'static,
+    ) -> Result<T, ShareError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let loaded = store.loaded();
+            let key = signing_key(&loaded.file, store.key.as_deref()).ok_or(ShareError::Invalid)?;
+            f(&loaded.file, &key)
+        })
+        .await
+        .unwrap_or(Err(ShareError::Invalid))
+    }
+
+    // 在阻塞线程中修改链接并写回：持有文件锁后重新读取，不会覆盖子命令同时做的创建和吊销；f 返回错误时不写入
+    async fn update<T: Send + 'static>(
+        self: &Arc<Self>,
+        f: impl FnOnce(&mut ShareFile) -> Result<T, ShareError> + Send + 'static,
+    ) -> Result<T, ShareError> {
+        let store = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut loaded = store.loaded.lock().unwrap();
+            let _lock = store::lock(&store.path, KIND).map_err(|e| {
+                tracing::warn!("{}", e);
+                ShareError::Unavailable
+            })?;
+            let modified = store::modified(&store.path);
+            let file = store::read_toml(&store.path, KIND).map_err(|e| {
+                tracing::warn!("无法重新加载分享链接文件: {}", e);
+                ShareError::Unavailable
+            })?;
+            *loaded = Loaded { modified, file };
+            let result = f(&mut loaded.file)?;
+            if let Err(e) = store::write_toml(&store.path, &loaded.file, KIND) {
+                tracing::warn!("无法保存分享链接文件: {}", e);
+                // 没有写入的修改不能生效，下次使用时从文件重新加载
+                loaded.modified = None;
+                return Err(ShareError::Unavailable);
+            }
+            loaded.modified = store::modified(&store.path);
+            Ok(result)
+        })
+        .await
+        .unwrap_or(Err(ShareError::Unavailable))
+    }
+
+    // 文件被子命令修改后重新读取；读取失败时不再接受任何链接
+    fn loaded(&self) -> std::sync::MutexGuard<'_, Loaded> {
+        let mut loaded = self.loaded.lock().unwrap();
+        let modified = store::modified(&self.path);
+        if loaded.modified != modified {
+            let file = store::read_toml(&self.path, KIND).unwrap_or_else(|e| {
+                tracing::warn!("无法重新加载分享链接文件: {}", e);
+                ShareFile::default()
+            });
+            *loaded = Loaded { modified, file };
+        }
+        loaded
+    }
+}
+
+// 按 <ID>.<签名> 查找链接并校验签名
+fn find<'a, T: Signed>(links: &'a [T], key: &[u8], token: &str) -> Result<&'a T, ShareError> {
+    let (id, signature) = token.split_once('.').ok_or(ShareError::Invalid)?;
+    let link = links.iter().find(|link| link.id() == id).ok_or(ShareError::Invalid)?;
+    if !auth::constant_time_eq(link.signature(key).as_bytes(), signature.as_bytes()) {
+        return Err(ShareError::Invalid);
+    }
+    Ok(link)
+}
+
+// 配置的密钥优先，否则使用文件中自动生成的密钥
+fn signing_key(file: &ShareFile, configured_key: Option<&[u8]>) -> Option<Vec<u8>> {
+    match configured_key {
+        Some(key) => Some(key.to_vec()),
+        None => file.key.as_ref().map(|key| key.as_bytes().to_vec()),
+    }
+}
+
+// 创建分享链接，返回 /s/ 之后的令牌；base_dir 用于确认分享的路径存在
+pub fn create(config: &ShareConfig, base_dir: &StdPath, new: NewShare) -> ShareResult<String> {
+    let path = mtls::normalize_path(&new.path);
+    let target = base_dir.join(path.trim_start_matches('/'));
+    if !target.exists() {
+        return Err(format!("'{}' 不存在", target.display()).into());
+    }
+
+    let _lock = store::lock(&config.file, KIND)?;
+    let (mut file, key) = open_for_create(config)?;
+    let link = ShareLink {
+        id: new_id(&file),
+        path,
+        created: tokens::unix_now(),
+        expires: new.expires,
+        max_downloads: new.max_downloads,
+        downloads: 0,
+    };
+    let token = link.token(&key);
+    file.links.push(link);
+    store::write_toml(&config.file, &file, KIND)?;
+    Ok(token)
+}
+
+// 创建上传链接，返回 /u/ 之后的令牌；接收文件的目录必须已经存在
+pub fn create_upload(config: &ShareConfig, base_dir: &StdPath, new: NewUploadLink) -> ShareResult<String> {
+    let path = mtls::normalize_path(&new.path);
+    let target = base_dir.join(path.trim_start_matches('/'));
+    if !target.is_dir() {
+        return Err(format!("'{}' 不存在或不是一个目录", target.display()).into());
+    }
+
+    let _lock = store::lock(&config.file, KIND)?;
+    let (mut file, key) = open_for_create(config)?;
+    let link = UploadLink {
+        id: new_id(&file),
+        path,
+        created: tokens::unix_now(),
+        expires: new.expires,
+        max_files: new.max_files,
+        max_bytes: new.max_bytes,
+        files: 0,
+        bytes: 0,
+    };
+    let token = link.token(&key);
+    file.uploads.push(link);
+    store::write_toml(&config.file, &file, KIND)?;
+    Ok(token)
+}
+
+// 读取分享链接文件；没有配置密钥时在文件中生成一个
+fn open_for_create(config: &ShareConfig) -> ShareResult<(ShareFile, Vec<u8>)> {
+    let mut file: ShareFile = store::read_toml(&config.file, KIND)?;
+    if config.key.is_none() && file.key.is_none() {
+        file.key = Some(tokens::random_hex(32));
+    }
+    let key = signing_key(&file, config.key.as_ref().map(|key| key.as_bytes())).unwrap();
+    Ok((file, key))
+}
+
+// 下载链接和上传链接共用 ID 空间，吊销时不必区分
+fn new_id(file: &ShareFile) -> String {
+    loop {
+        let id = tokens::random_hex(6);
+        if !file.links.iter().any(|link| link.id == id) && !file.uploads.iter().any(|link| link.id == id) {
+            break id;
+        }
+    }
+}
+
+// 吊销下载链接或上传链接，返回链接对应的路径
+pub fn revoke(config: &ShareConfig, id: &str) -> ShareResult<String> {
+    let _lock = store::lock(&config.file, KIND)?;
+    let mut file: ShareFile = store::read_toml(&config.file, KIND)?;
+    let path = if let Some(index) = file.links.iter().position(|link| link.id == id) {
+        file.links.remove(index).path
+    } else if let Some(index) = file.uploads.iter().position(|link| link.id == id) {
+        file.uploads.remove(index).path
+    } else {
+        return Err(format!("没有 ID 为 '{}' 的分享链接", id).into());
+    };
+    store::write_toml(&config.file, &file, KIND)?;
+    Ok(path)
+}
+
+// 打印分享链接列表；base_url 不为空时同时打印完整链接
+pub fn print_list(config: &ShareConfig, base_url: &str) -> ShareResult<()> {
+    let file: ShareFile = store::read_toml(&config.file, KIND)?;
+    if file.links.is_empty() && file.uploads.is_empty() {
+        println!("没有分享链接");
+        return Ok(());
+    }
+    let key = signing_key(&file, config.key.as_ref().map(|key| key.as_bytes()));
+    let url = |prefix: &str, link: &dyn Signed| {
+        key.as_ref()
+            .map(|key| format!("{}/{}/{}", base_url, prefix, link.token(key)))
+            .unwrap_or_default()
+    };
+    let now = tokens::unix_now();
+
+    if !file.links.is_empty() {
+        println!("{:<14} {:<30} {:<10} {:<31} 链接", "ID", "路径", "下载次数", "过期时间");
+        for link in &file.links {
+            let downloads = match link.max_downloads {
+                Some(max) => format!("{}/{}", link.downloads, max),
+                None => link.downloads.to_string(),
+            };
+            let expires = expiry_text(link.expires, link.usable(now).is_ok());
+            println!("{:<14} {:<30} {:<10} {:<31} {}", link.id, link.path, downloads, expires, url("s", link));
+        }
+    }
+
+    if !file.uploads.is_empty() {
+        if !file.links.is_empty() {
+            println!();
+        }
+        println!("{:<14} {:<30} {:<10} {:<22} {:<31} 上传链接", "ID", "目录", "文件数", "大小", "过期时间");
+        for link in &file.uploads {
+            let files = match link.max_files {
+                Some(max) => format!("{}/{}", link.files, max),
+                None => link.files.to_string(),
+            };
+            let bytes = match link.max_bytes {
+                Some(max) => format!("{}/{}", crate::format_file_size(link.bytes), crate::format_file_size(max)),
+                None => crate::format_file_size(link.bytes),
+            };
+            let expires = expiry_text(link.expires, link.usable(now).is_ok());
+            println!(
+                "{:<14} {:<30} {:<10} {:<22} {:<31} {}",
+                link.id,
+                link.path,
+                files,
+                bytes,
+                expires,
+                url("u", link)
+            );
+        }
+    }
+    Ok(())
+}
+
+fn expiry_text(expires: Option<u64>, usable: bool) -> String {
+    let mut text = match expires {
+        Some(expires) => httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(expires)),
+        None => "永不过期".to_string(),
+    };
+    if !usable {
+        text.push_str("（已失效）");
+    }
+    text
+}
+
+// 上传链接的页面：只有上传表单，不显示目录中已有的文件；target 为 None 时链接已不可用
+pub fn render_upload_page(token: &str, target: Option<&UploadTarget>, notice: Option<&str>, csrf: &CsrfToken) -> String {
+    let notice = notice
+        .map(|notice| format!(r#"<div class="notice">{}</div>"#, html_escape(notice)))
+        .unwrap_or_default();
+    let body = match target {
+        Some(target) => {
+            let mut limits = Vec::new();
+            if let Some(files) = target.remaining_files {
+                limits.push(format!("还可以上传 {} 个文件", files));
+            }
+            if let Some(bytes) = target.remaining_bytes {
+                limits.push(format!("剩余容量 {}", crate::format_file_size(bytes)));
+            }
+            if let Some(expires) = target.expires {
+                limits.push(format!(
+                    "链接有效期至 {}",
+                    httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(expires))
+                ));
+            }
+            let limits: String = limits.iter().map(|limit| format!("<li>{}</li>", limit)).collect();
+            format!(
+                r#"<p>通过此链接只能上传文件，不会列出目标文件夹中的内容。</p>
+            <ul>{}</ul>
+            <form action="/u/{}" method="post" enctype="multipart/form-data">
+                {}
+                <input type="file" name="file" multiple required>
+                <button type="submit">上传</button>
+            </form>"#,
+                limits,
+                html_escape(token),
+                csrf.form_field()
+            )
+        }
+        None => "<p>此上传链接已过期或额度已用完。</p>".to_string(),
+    };
+    format!(r#"
+<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <meta name="robots" content="noindex">
+    <title>上传文件</title>
+    <style>
+        body {{
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
+            margin: 0;
+            padding: 20px;
+            background-color: #f5f5f5;
+        }}
+        .container {{
+            max-width: 480px;
+            margin: 60px auto;
+            background: white;
+            border-radius: 8px;
+            box-shadow: 0 2px 10px rgba(0,0,0,0.1);
+            overflow: hidden;
+        }}
+        .header {{
+            background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+            color: white;
+            padding: 20px;
+            text-align: center;
+        }}
+        .header h1 {{
+            margin: 0;
+            font-size: 1.5rem;
+        }}
+        .content {{
+            padding: 20px;
+            color: #495057;
+        }}
+        form {{
+            display: flex;
+            flex-direction: column;
+            gap: 12px;
+        }}
+        button {{
+            padding: 10px;
+            background-color: #007bff;
+            color: white;
+            border: none;
+            border-radius: 4px;
+            font-size: 1rem;
+            cursor: pointer;
+        }}
+        button:hover {{
+            background-color: #0069d9;
+        }}
+        .notice {{
+            padding: 10px;
+            background-color: #e9f5ee;
+            border-radius: 4px;
+            color: #1e7e34;
+        }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <div class="header">
+            <h1>📤 上传文件</h1>
+        </div>
+        <div class="content">
+            {}
+            {}
+        </div>
+    </div>
+</body>
+</html>
+    "#, notice, body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 写回下载次数前要在锁内重新读取，不能用内存中的旧内容覆盖子命令刚创建的链接
+    #[tokio::test]
+    async fn recording_a_download_keeps_links_created_meanwhile() {
+        let dir = std::env::temp_dir().join(format!("myhs-shares-{}", tokens::random_hex(4)));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), "a").unwrap();
+        let config = ShareConfig {
+            file: dir.join("shares.toml"),
+            key: None,
+        };
+        let new = |max_downloads| NewShare {
+            path: "a.txt".to_string(),
+            expires: None,
+            max_downloads,
+        };
+
+        let limited = create(&config, &dir, new(Some(2))).unwrap();
+        let store = Arc::new(ShareStore::open(&config).unwrap());
+        let created = create(&config, &dir, new(None)).unwrap();
+        // 修改时间的精度不够时服务器察觉不到文件被改过
+        store.loaded.lock().unwrap().modified = store::modified(&config.file);
+
+        let shared = store.resolve(&limited).await.unwrap();
+        store.record_download(&shared.id).await.unwrap();
+        let file: ShareFile = store::read_toml(&config.file, KIND).unwrap();
+        let resolved = store.resolve(&created).await;
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(file.links.len(), 2);
+        assert_eq!(file.links[0].downloads, 1);
+        assert!(resolved.is_ok());
+    }
+}
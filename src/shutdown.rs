@@ -0,0 +1,144 @@
+use axum::{
+    body::{Body, Bytes, HttpBody},
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use http_body::{Frame, SizeHint};
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Instant;
+
+// 等待排空期间打印进度的间隔
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(5);
+
+// 等待 Ctrl+C（SIGINT）或 SIGTERM
+pub async fn signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c().await.expect("无法监听 Ctrl+C 信号");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("无法监听 SIGTERM 信号")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+// 正在处理的请求数；下载请求在响应体发送完毕后才算结束
+#[derive(Debug, Default)]
+pub struct InFlight {
+    count: AtomicUsize,
+}
+
+impl InFlight {
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    fn enter(self: &Arc<Self>) -> InFlightGuard {
+        self.count.fetch_add(1, Ordering::SeqCst);
+        InFlightGuard(self.clone())
+    }
+}
+
+struct InFlightGuard(Arc<InFlight>);
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.0.count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// 统计进行中请求的中间件
+pub async fn track_in_flight(
+    State(in_flight): State<Arc<InFlight>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let guard = in_flight.enter();
+    let response = next.run(request).await;
+    response.map(|body| {
+        Body::new(TrackedBody {
+            inner: body,
+            _guard: guard,
+        })
+    })
+}
+
+// 持有计数守卫的响应体，保留原响应体的长度信息
+struct TrackedBody {
+    inner: Body,
+    _guard: InFlightGuard,
+}
+
+impl HttpBody for TrackedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+// 排空结果
+pub enum Drain {
+    Completed,
+    TimedOut(usize),
+    Forced(usize),
+}
+
+// 等待所有服务退出，超时或再次收到停止信号时放弃等待
+pub async fn drain<F>(servers: F, timeout: Duration, in_flight: &InFlight) -> Drain
+where
+    F: Future,
+{
+    tokio::pin!(servers);
+    let deadline = Instant::now() + timeout;
+    let mut progress = tokio::time::interval_at(Instant::now() + PROGRESS_INTERVAL, PROGRESS_INTERVAL);
+
+    loop {
+        tokio::select! {
+            _ = &mut servers => return Drain::Completed,
+            _ = tokio::time::sleep_until(deadline) => return Drain::TimedOut(in_flight.count()),
+            _ = signal() => return Drain::Forced(in_flight.count()),
+            _ = progress.tick() => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                eprintln!(
+                    "⏳ 仍有 {} 个请求未完成，最多再等待 {} 秒",
+                    in_flight.count(),
+                    remaining.as_secs()
+                );
+            }
+        }
+    }
+}
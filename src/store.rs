@@ -0,0 +1,52 @@
+use serde::{de::DeserializeOwned, Serialize};
+use std::{path::Path as StdPath, time::SystemTime};
+
+type StoreResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+// 由子命令维护、服务器运行中按修改时间重新读取的 TOML 文件（令牌、分享链接等）
+
+// 读取文件，不存在时返回默认值；kind 用于错误信息，例如 "令牌文件"
+pub fn read_toml<T: DeserializeOwned + Default>(path: &StdPath, kind: &str) -> StoreResult<T> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => toml::from_str(&text).map_err(|e| format!("{} '{}' 格式错误: {}", kind, path.display(), e).into()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(format!("无法读取{} '{}': {}", kind, path.display(), e).into()),
+    }
+}
+
+// 先写临时文件再改名，正在运行的服务器不会读到写了一半的文件；Unix 上只有所有者可读写
+pub fn write_toml<T: Serialize>(path: &StdPath, value: &T, kind: &str) -> StoreResult<()> {
+    let text = toml::to_string(value)?;
+    let temp = path.with_extension("tmp");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let write = || -> std::io::Result<()> {
+        use std::io::Write;
+        options.open(&temp)?.write_all(text.as_bytes())?;
+        std::fs::rename(&temp, path)
+    };
+    write().map_err(|e| format!("无法写入{} '{}': {}", kind, path.display(), e).into())
+}
+
+// 读改写文件期间持有的排他锁，加在同目录的 <文件名>.lock 上（文件本身会被改名替换）；
+// 服务器和子命令都先加锁再读取，不会覆盖对方的修改。返回的文件关闭时释放锁
+pub fn lock(path: &StdPath, kind: &str) -> StoreResult<std::fs::File> {
+    let lock_path = path.with_extension("lock");
+    let lock = || -> std::io::Result<std::fs::File> {
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)?;
+        file.lock()?;
+        Ok(file)
+    };
+    lock().map_err(|e| format!("无法锁定{} '{}': {}", kind, path.display(), e).into())
+}
+
+// 文件的修改时间，用于判断是否需要重新读取
+pub fn modified(path: &StdPath) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,167 @@
+use axum::http::HeaderValue;
+use rcgen::{CertificateParams, DnType, KeyPair};
+use rustls::{
+    pki_types::{CertificateDer, PrivateKeyDer},
+    server::WebPkiClientVerifier,
+    RootCertStore, ServerConfig,
+};
+use sha2::{Digest, Sha256};
+use std::{
+    fs::File,
+    io::BufReader,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    path::{Path as StdPath, PathBuf},
+    sync::Arc,
+};
+
+use crate::listener::BindSpec;
+
+type TlsResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+// 证书来源
+#[derive(Debug, Clone)]
+pub enum TlsSource {
+    // PEM 格式的证书链和私钥
+    Files { cert: PathBuf, key: PathBuf },
+    // 启动时为本机地址生成自签名证书
+    SelfSigned,
+}
+
+// HTTPS 配置
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub source: TlsSource,
+    // 要求客户端出示由这些 CA 签发的证书
+    pub client_ca: Option<PathBuf>,
+    // 只负责跳转到 HTTPS 的 HTTP 监听地址
+    pub redirect_http: Vec<BindSpec>,
+    pub hsts: Option<Hsts>,
+    // 在同一端口上通过 UDP 提供 HTTP/3
+    pub http3: bool,
+}
+
+// Strict-Transport-Security 响应头的设置
+#[derive(Debug, Clone, Copy)]
+pub struct Hsts {
+    // 为 0 时通知浏览器清除之前记住的 HSTS
+    pub max_age: u64,
+    pub include_subdomains: bool,
+    pub preload: bool,
+}
+
+impl Hsts {
+    pub fn header_value(&self) -> HeaderValue {
+        let mut value = format!("max-age={}", self.max_age);
+        if self.include_subdomains {
+            value.push_str("; includeSubDomains");
+        }
+        if self.preload {
+            value.push_str("; preload");
+        }
+        HeaderValue::from_str(&value).unwrap()
+    }
+}
+
+// 准备好的 TLS 配置
+pub struct Tls {
+    pub config: Arc<ServerConfig>,
+    // 叶子证书的 SHA-256 指纹
+    pub fingerprint: String,
+    pub self_signed: bool,
+    // 是否要求客户端证书
+    pub client_auth: bool,
+}
+
+impl Tls {
+    // 加载或生成证书；hosts 是自签名证书需要覆盖的地址
+    pub fn load(tls: &TlsConfig, hosts: &[IpAddr]) -> TlsResult<Tls> {
+        let (certs, key) = match &tls.source {
+            TlsSource::Files { cert, key } => (load_certs(cert)?, load_key(key)?),
+            TlsSource::SelfSigned => self_signed(hosts)?,
+        };
+        let fingerprint = fingerprint(&certs[0]);
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone()).with_safe_default_protocol_versions()?;
+        let builder = match &tls.client_ca {
+            Some(path) => {
+                let mut roots = RootCertStore::empty();
+                for cert in load_certs(path)? {
+                    roots
+                        .add(cert)
+                        .map_err(|e| format!("CA 证书文件 '{}' 无效: {}", path.display(), e))?;
+                }
+                let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider)
+                    .build()
+                    .map_err(|e| format!("无法使用 CA 证书文件 '{}': {}", path.display(), e))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder
+            .with_single_cert(certs, key)
+            .map_err(|e| format!("证书与私钥不匹配或格式不受支持: {}", e))?;
+        // 通过 ALPN 优先协商 HTTP/2
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+
+        Ok(Tls {
+            config: Arc::new(config),
+            fingerprint,
+            self_signed: matches!(tls.source, TlsSource::SelfSigned),
+            client_auth: tls.client_ca.is_some(),
+        })
+    }
+}
+
+// 读取 PEM 证书链，叶子证书在前
+fn load_certs(path: &StdPath) -> TlsResult<Vec<CertificateDer<'static>>> {
+    let file = File::open(path).map_err(|e| format!("无法读取证书文件 '{}': {}", path.display(), e))?;
+    let certs = rustls_pemfile::certs(&mut BufReader::new(file))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("证书文件 '{}' 格式错误: {}", path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("证书文件 '{}' 中没有找到 PEM 证书", path.display()).into());
+    }
+    Ok(certs)
+}
+
+// 读取 PEM 私钥，支持 PKCS#8、PKCS#1（RSA）和 SEC1（EC）格式
+fn load_key(path: &StdPath) -> TlsResult<PrivateKeyDer<'static>> {
+    let file = File::open(path).map_err(|e| format!("无法读取私钥文件 '{}': {}", path.display(), e))?;
+    rustls_pemfile::private_key(&mut BufReader::new(file))
+        .map_err(|e| format!("私钥文件 '{}' 格式错误: {}", path.display(), e))?
+        .ok_or_else(|| format!("私钥文件 '{}' 中没有找到 PEM 私钥", path.display()).into())
+}
+
+// 生成覆盖 localhost、回环地址和 hosts 的自签名证书
+fn self_signed(hosts: &[IpAddr]) -> TlsResult<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>)> {
+    let mut ips = vec![IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)];
+    for ip in hosts {
+        if !ips.contains(ip) {
+            ips.push(*ip);
+        }
+    }
+    let mut names = vec!["localhost".to_string()];
+    names.extend(ips.iter().map(IpAddr::to_string));
+
+    let mut params = CertificateParams::new(names).map_err(|e| format!("无法生成自签名证书: {}", e))?;
+    params.distinguished_name.push(DnType::CommonName, "MyHS self-signed");
+    let key_pair = KeyPair::generate().map_err(|e| format!("无法生成私钥: {}", e))?;
+    let cert = params
+        .self_signed(&key_pair)
+        .map_err(|e| format!("无法生成自签名证书: {}", e))?;
+
+    Ok((
+        vec![cert.der().clone()],
+        PrivateKeyDer::Pkcs8(key_pair.serialize_der().into()),
+    ))
+}
+
+// 形如 AB:CD:... 的 SHA-256 指纹，便于和浏览器中显示的证书信息对照
+fn fingerprint(cert: &CertificateDer<'_>) -> String {
+    Sha256::digest(cert.as_ref())
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect::<Vec<_>>()
+        .join(":")
+}
@@ -0,0 +1,428 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    path::{Path as StdPath, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::{accounts::Permission, auth, mtls, store};
+
+type TokenResult<T> = Result<T, Box<dyn std::error::Error>>;
+
+// 令牌的固定前缀，便于在日志和代码仓库中识别泄露的令牌
+const TOKEN_PREFIX: &str = "myhs_";
+
+const KIND: &str = "令牌文件";
+
+// 令牌可以授予的范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    // 下载文件和查看目录列表
+    Read,
+    // 上传新文件
+    Upload,
+    // 删除或覆盖已有文件
+    Delete,
+}
+
+impl Scope {
+    fn grants(self, permission: Permission) -> bool {
+        match self {
+            Scope::Read => matches!(permission, Permission::Read | Permission::List),
+            Scope::Upload => permission == Permission::Upload,
+            Scope::Delete => permission == Permission::Delete,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Scope::Read => "read",
+            Scope::Upload => "upload",
+            Scope::Delete => "delete",
+        }
+    }
+}
+
+// 令牌文件的内容；只保存令牌密钥的 SHA-256 摘要
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TokenFile {
+    #[serde(default)]
+    tokens: Vec<StoredToken>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StoredToken {
+    id: String,
+    name: String,
+    hash: String,
+    scopes: Vec<Scope>,
+    paths: Vec<String>,
+    // Unix 时间戳（秒）
+    created: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<u64>,
+}
+
+impl StoredToken {
+    fn expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+}
+
+// 通过校验的 API 令牌，作为请求扩展传给处理器
+#[derive(Debug, Clone)]
+pub struct ApiToken {
+    pub name: String,
+    scopes: Vec<Scope>,
+    paths: Vec<String>,
+}
+
+impl ApiToken {
+    // 令牌只能在自己的路径前缀下使用被授予的范围
+    pub fn allows(&self, path: &str, permission: Permission) -> bool {
+        let path = mtls::normalize_path(path);
+        self.scopes.iter().any(|scope| scope.grants(permission))
+            && self.paths.iter().any(|prefix| mtls::path_within(&path, prefix))
+    }
+}
+
+// 创建令牌时的参数
+#[derive(Debug, Clone)]
+pub struct NewToken {
+    pub name: String,
+    pub scopes: Vec<Scope>,
+    pub paths: Vec<String>,
+    pub expires: Option<u64>,
+}
+
+#[derive(Debug)]
+struct Loaded {
+    modified: Option<SystemTime>,
+    tokens: Vec<StoredToken>,
+}
+
+// 磁盘上的令牌文件；文件被 `MyHS token` 修改后，下一个请求会重新读取，吊销立即生效
+#[derive(Debug)]
+pub struct TokenStore {
+    path: PathBuf,
+    loaded: Mutex<Loaded>,
+}
+
+impl TokenStore {
+    // 打开令牌文件，文件不存在时视为没有令牌
+    pub fn open(path: &StdPath) -> TokenResult<TokenStore> {
+        let loaded = Loaded {
+            modified: store::modified(path),
+            tokens: store::read_toml::<TokenFile>(path, KIND)?.tokens,
+        };
+        Ok(TokenStore {
+            path: path.to_path_buf(),
+            loaded: Mutex::new(loaded),
+        })
+    }
+
+    // 未过期的令牌数量
+    pub fn active_count(&self) -> usize {
+        let now = unix_now();
+        self.loaded.lock().unwrap().tokens.iter().filter(|t| !t.expired(now)).count()
+    }
+
+    // 校验请求中的令牌，失败时返回原因
+    fn authenticate(&self, token: &str) -> Result<ApiToken, &'static str> {
+        let (id, secret) = token
+            .strip_prefix(TOKEN_PREFIX)
+            .and_then(|rest| rest.split_once('_'))
+            .ok_or("令牌格式无效")?;
+
+        let mut loaded = self.loaded.lock().unwrap();
+        let modified = store::modified(&self.path);
+        if loaded.modified != modified {
+            // 读取失败时不再接受任何令牌，避免已吊销的令牌继续有效
+            let tokens = match store::read_toml::<TokenFile>(&self.path, KIND) {
+                Ok(file) => file.tokens,
+                Err(e) => {
+                    tracing::warn!("无法重新加载令牌文件: {}", e);
+                    Vec::new()
+                }
+            };
+            *loaded = Loaded { modified, tokens };
+        }
+
+        let stored = loaded
+            .tokens
+            .iter()
+            .find(|stored| stored.id == id && auth::constant_time_eq(stored.hash.as_bytes(), hash_secret(secret).as_bytes()))
+            .ok_or("令牌无效或已被吊销")?;
+        if stored.expired(unix_now()) {
+            return Err("令牌已过期");
+        }
+        Ok(ApiToken {
+            name: stored.name.clone(),
+            scopes: stored.scopes.clone(),
+            paths: stored.paths.clone(),
+        })
+    }
+}
+
+// 创建令牌并写入文件，返回完整的令牌字符串（只在此时可见）
+pub fn create(path: &StdPath, new: NewToken) -> TokenResult<String> {
+    // 同时运行的其他 token 命令不会覆盖这次修改
+    let _lock = store::lock(path, KIND)?;
+    let mut file = store::read_toml::<TokenFile>(path, KIND)?;
+    if file.tokens.iter().any(|token| token.name == new.name) {
+        return Err(format!("已存在名为 '{}' 的令牌", new.name).into());
+    }
+
+    let id = loop {
+        let id = random_hex(4);
+        if !file.tokens.iter().any(|token| token.id == id) {
+            break id;
+        }
+    };
+    let secret = random_hex(32);
+    let paths = if new.paths.is_empty() {
+        vec!["/".to_string()]
+    } else {
+        new.paths.iter().map(|path| mtls::normalize_path(path)).collect()
+    };
+    file.tokens.push(StoredToken {
+        id: id.clone(),
+        name: new.name,
+        hash: hash_secret(&secret),
+        scopes: new.scopes,
+        paths,
+        created: unix_now(),
+        expires: new.expires,
+    });
+    store::write_toml(path, &file, KIND)?;
+    Ok(format!("{}{}_{}", TOKEN_PREFIX, id, secret))
+}
+
+// 按 ID 或名称吊销令牌，返回被删除的令牌名称
+pub fn revoke(path: &StdPath, id_or_name: &str) -> TokenResult<String> {
+    let _lock = store::lock(path, KIND)?;
+    let mut file = store::read_toml::<TokenFile>(path, KIND)?;
+    let index = file
+        .tokens
+        .iter()
+        .position(|token| token.id == id_or_name || token.name == id_or_name)
+        .ok_or_else(|| format!("没有 ID 或名称为 '{}' 的令牌", id_or_name))?;
+    let removed = file.tokens.remove(index);
+    store::write_toml(path, &file, KIND)?;
+    Ok(removed.name)
+}
+
+// 打印令牌列表
+pub fn print_list(path: &StdPath) -> TokenResult<()> {
+    let file = store::read_toml::<TokenFile>(path, KIND)?;
+    if file.tokens.is_empty() {
+        println!("没有令牌");
+        return Ok(());
+    }
+    let now = unix_now();
+    println!("{:<10} {:<20} {:<20} {:<24} 过期时间", "ID", "名称", "范围", "路径");
+    for token in &file.tokens {
+        let scopes: Vec<_> = token.scopes.iter().map(|scope| scope.name()).collect();
+        let expires = match token.expires {
+            None => "永不过期".to_string(),
+            Some(expires) => {
+                let date = httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(expires));
+                if token.expired(now) {
+                    format!("{}（已过期）", date)
+                } else {
+                    date
+                }
+            }
+        };
+        println!(
+            "{:<10} {:<20} {:<20} {:<24} {}",
+            token.id,
+            token.name,
+            scopes.join(","),
+            token.paths.join(","),
+            expires
+        );
+    }
+    Ok(())
+}
+
+// 过期时间：相对时长（30d、12h、90m、3600s）或日期 YYYY-MM-DD（该日 UTC 结束时过期）
+pub fn parse_expiry(value: &str) -> Result<u64, String> {
+    let invalid = || format!("'{}' 不是有效的过期时间，应为 30d、12h、90m 这样的时长或 YYYY-MM-DD 日期", value);
+    let value = value.trim();
+
+    if let Some((unit, number)) = value
+        .char_indices()
+        .last()
+        .filter(|(_, unit)| unit.is_ascii_alphabetic())
+        .map(|(index, unit)| (unit, &value[..index]))
+    {
+        let multiplier: u64 = match unit.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => return Err(invalid()),
+        };
+        return number
+            .parse::<u64>()
+            .ok()
+            .and_then(|number| number.checked_mul(multiplier))
+            .and_then(|seconds| seconds.checked_add(unix_now()))
+            .ok_or_else(invalid);
+    }
+
+    let parts: Vec<&str> = value.split('-').collect();
+    let [year, month, day] = parts.as_slice() else {
+        return Err(invalid());
+    };
+    let (year, month, day): (i64, u32, u32) = (
+        year.parse().map_err(|_| invalid())?,
+        month.parse().map_err(|_| invalid())?,
+        day.parse().map_err(|_| invalid())?,
+    );
+    // 年份限制在四位数以内，换算成秒时不会溢出
+    if !(1970..=9999).contains(&year) {
+        return Err(invalid());
+    }
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return Err(invalid()),
+    };
+    if day == 0 || day > days_in_month {
+        return Err(invalid());
+    }
+    let end_of_day = (days_from_civil(year, month, day) + 1) * 24 * 60 * 60;
+    u64::try_from(end_of_day).map_err(|_| invalid())
+}
+
+// 公历日期距 1970-01-01 的天数
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// 校验 Authorization: Bearer 令牌的中间件；没有带令牌的请求交给其他认证方式处理
+pub async fn authenticate(State(tokens): State<Option<Arc<TokenStore>>>, mut request: Request, next: Next) -> Response {
+    let (Some(tokens), Some(token)) = (tokens, bearer_token(request.headers())) else {
+        return next.run(request).await;
+    };
+    // 令牌文件被修改后要重新读取，文件读写放到阻塞线程中进行，不占用异步工作线程
+    let checked = tokio::task::spawn_blocking(move || tokens.authenticate(&token))
+        .await
+        .unwrap_or(Err("令牌校验失败"));
+    match checked {
+        Ok(api_token) => {
+            request.extensions_mut().insert(api_token);
+            next.run(request).await
+        }
+        Err(reason) => {
+            tracing::info!("拒绝 API 令牌: {}", reason);
+            (
+                StatusCode::UNAUTHORIZED,
+                [(header::WWW_AUTHENTICATE, "Bearer error=\"invalid_token\"")],
+                reason,
+            )
+                .into_response()
+        }
+    }
+}
+
+// 令牌有效但没有所需的范围或路径
+pub fn forbidden(token: &ApiToken, path: &str) -> Response {
+    tracing::info!("令牌 {} 无权访问 {}", token.name, path);
+    (
+        StatusCode::FORBIDDEN,
+        [(header::WWW_AUTHENTICATE, "Bearer error=\"insufficient_scope\"")],
+        "API 令牌没有该路径的权限",
+    )
+        .into_response()
+}
+
+fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    let value = headers.get(header::AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    scheme.eq_ignore_ascii_case("bearer").then(|| token.trim().to_string())
+}
+
+fn hash_secret(secret: &str) -> String {
+    Sha256::digest(secret.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn random_hex(len: usize) -> String {
+    let mut bytes = vec![0u8; len];
+    getrandom::getrandom(&mut bytes).expect("无法获取随机数");
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_relative_durations() {
+        let now = unix_now();
+        let in_range = |value: &str, seconds: u64| {
+            let expiry = parse_expiry(value).unwrap();
+            assert!((now + seconds..=now + seconds + 5).contains(&expiry), "{}", value);
+        };
+        in_range("3600s", 3600);
+        in_range("90m", 90 * 60);
+        in_range("12H", 12 * 60 * 60);
+        in_range(" 30d ", 30 * 24 * 60 * 60);
+    }
+
+    #[test]
+    fn parses_dates_as_end_of_day_utc() {
+        assert_eq!(parse_expiry("1970-01-01").unwrap(), 86400);
+        assert_eq!(parse_expiry("2024-02-29").unwrap(), 1_709_251_200);
+        assert!(parse_expiry("2023-02-29").is_err());
+        assert!(parse_expiry("2024-13-01").is_err());
+        assert!(parse_expiry("2024-04-31").is_err());
+    }
+
+    #[test]
+    fn rejects_overflowing_expiry() {
+        for value in [
+            format!("{}d", u64::MAX),
+            format!("{}s", u64::MAX),
+            format!("{}h", u64::MAX / 60),
+            format!("{}m", u64::MAX / 60 + 1),
+            "99999999999999999999d".to_string(),
+            "9223372036854775807-12-31".to_string(),
+            "-9223372036854775808-01-01".to_string(),
+        ] {
+            assert!(parse_expiry(&value).is_err(), "{}", value);
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_expiry() {
+        for value in ["", "d", "10", "10w", "-5d", "1.5h", "2024-01", "2024/01/01"] {
+            assert!(parse_expiry(value).is_err(), "{}", value);
+        }
+    }
+}
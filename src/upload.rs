@@ -0,0 +1,982 @@
+use axum::{
+    extract::{multipart::MultipartError, Multipart},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    future::Future,
+    io,
+    path::{Component, Path as StdPath, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::{
+    accounts::{html_escape, Permission},
+    private::PrivateFiles,
+    tokens,
+};
+
+// 默认的上传请求大小上限
+pub const DEFAULT_MAX_REQUEST_SIZE: u64 = 4 << 30;
+
+// 上传临时文件的名称：.myhs-upload-<随机十六进制>.part，写在目标目录中，完整收到后改名
+const TEMP_PREFIX: &str = ".myhs-upload-";
+const TEMP_SUFFIX: &str = ".part";
+
+// 文件名的最大字节数，常见文件系统的上限都是 255
+const MAX_NAME_BYTES: usize = 255;
+
+// Windows 的设备名，不论带不带扩展名都不能用作文件名
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8", "COM9", "LPT1",
+    "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+// 自动改名时最多尝试的编号
+const MAX_RENAME_ATTEMPTS: u32 = 1000;
+
+// 上传文件夹时相对路径的最大层数
+const MAX_PATH_DEPTH: usize = 32;
+
+// 上传设置
+#[derive(Debug, Clone, Copy)]
+pub struct UploadConfig {
+    // 整个上传请求的大小上限
+    pub max_request_size: u64,
+    // 单个文件的大小上限，未设置时只受请求大小限制
+    pub max_file_size: Option<u64>,
+    // 改名前把文件内容刷到磁盘
+    pub fsync: bool,
+    // 已有同名文件时的默认处理方式，每个请求可以另外指定
+    pub on_conflict: ConflictPolicy,
+}
+
+// 上传的文件与已有文件同名时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    // 覆盖已有文件
+    #[default]
+    Overwrite,
+    // 保留两者，新文件改名为 name (1).ext
+    Rename,
+    // 不保存新文件
+    Skip,
+    // 不保存新文件，并以 409 响应
+    Fail,
+}
+
+impl ConflictPolicy {
+    pub fn name(self) -> &'static str {
+        match self {
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::Rename => "rename",
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Fail => "fail",
+        }
+    }
+}
+
+impl FromStr for ConflictPolicy {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "rename" => Ok(ConflictPolicy::Rename),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "fail" => Ok(ConflictPolicy::Fail),
+            _ => Err(format!("未知的同名文件处理方式 '{}'，可选 overwrite、rename、skip、fail", value)),
+        }
+    }
+}
+
+// 一次保存文件的选项
+#[derive(Debug, Clone, Copy)]
+pub struct SaveOptions<'a> {
+    pub max_file_size: Option<u64>,
+    pub fsync: bool,
+    pub on_conflict: ConflictPolicy,
+    // 是否接受表单中的 on_conflict 字段；它可以出现在 current_path 之后、第一个文件之前
+    pub conflict_field: bool,
+    // 是否接受 relative_path 给出的子目录
+    pub folders: bool,
+    // 配置文件等不能被上传的文件覆盖，也不能被抢先创建
+    pub private: &'a PrivateFiles,
+}
+
+// 中止整个上传请求的错误
+#[derive(Debug)]
+pub enum UploadError {
+    // 请求体读取失败，包括超过请求大小上限
+    Multipart(MultipartError),
+    // 表单字段无效或位置不对
+    Field(String),
+}
+
+impl IntoResponse for UploadError {
+    fn into_response(self) -> Response {
+        match self {
+            UploadError::Multipart(e) if e.status() == StatusCode::PAYLOAD_TOO_LARGE => {
+                tracing::info!("上传请求超过大小上限");
+                (StatusCode::PAYLOAD_TOO_LARGE, "上传请求超过服务器允许的大小上限").into_response()
+            }
+            UploadError::Multipart(e) => {
+                tracing::info!("上传请求读取失败: {}", e);
+                (e.status(), e.body_text()).into_response()
+            }
+            UploadError::Field(message) => (StatusCode::BAD_REQUEST, message).into_response(),
+        }
+    }
+}
+
+// 正在写入、尚未完成的上传文件
+#[derive(Debug, Default)]
+pub struct PartialUploads {
+    files: Mutex<HashSet<PathBuf>>,
+}
+
+impl PartialUploads {
+    // 登记一个即将写入的文件，返回的守卫在 finish 之前被丢弃时会删除该文件
+    pub fn track(self: &Arc<Self>, path: &StdPath) -> PartialUpload {
+        self.files.lock().unwrap().insert(path.to_path_buf());
+        PartialUpload {
+            uploads: self.clone(),
+            path: path.to_path_buf(),
+            finished: false,
+        }
+    }
+
+    // 删除所有未写完的文件，返回删除的数量
+    pub fn cleanup(&self) -> usize {
+        let files: Vec<PathBuf> = self.files.lock().unwrap().drain().collect();
+        files
+            .iter()
+            .filter(|path| std::fs::remove_file(path).is_ok())
+            .count()
+    }
+
+    // 删除 dir 下上次运行遗留的临时文件（进程被强制结束时来不及清理），返回删除的数量；
+    // 正在写入的文件已经登记，不会被误删
+    pub fn remove_stale(&self, dir: &StdPath) -> usize {
+        let Ok(entries) = std::fs::read_dir(dir) else {
+            return 0;
+        };
+        let mut removed = 0;
+        for entry in entries.flatten() {
+            // 不跟随符号链接，避免清理到共享目录以外
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let path = entry.path();
+            if file_type.is_dir() {
+                removed += self.remove_stale(&path);
+            } else if file_type.is_file() && is_temp_file(&entry.file_name().to_string_lossy()) {
+                let files = self.files.lock().unwrap();
+                if !files.contains(&path) && std::fs::remove_file(&path).is_ok() {
+                    tracing::info!("删除遗留的上传临时文件 {}", path.display());
+                    removed += 1;
+                }
+            }
+        }
+        removed
+    }
+}
+
+// 是否是上传临时文件；目录列表不显示这些文件，也不允许下载
+pub fn is_temp_file(name: &str) -> bool {
+    name.strip_prefix(TEMP_PREFIX)
+        .and_then(|rest| rest.strip_suffix(TEMP_SUFFIX))
+        .is_some_and(|id| !id.is_empty() && id.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+// 单个上传文件的写入守卫；请求中断或服务器停止时清理写了一半的文件
+#[derive(Debug)]
+pub struct PartialUpload {
+    uploads: Arc<PartialUploads>,
+    path: PathBuf,
+    finished: bool,
+}
+
+impl PartialUpload {
+    // 文件已完整写入
+    pub fn finish(mut self) {
+        self.finished = true;
+    }
+}
+
+impl Drop for PartialUpload {
+    fn drop(&mut self) {
+        // 已被 cleanup 取走的文件不再重复处理
+        let tracked = self.uploads.files.lock().unwrap().remove(&self.path);
+        if tracked && !self.finished {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+// 单个文件的上传结果
+#[derive(Debug)]
+pub enum Outcome {
+    // 已保存，附带最终的文件名
+    Saved(String),
+    // 覆盖了同名文件
+    Overwritten(String),
+    // 已有同名文件，改名保存
+    Renamed(String),
+    // 已有同名文件，按 skip 跳过
+    Skipped,
+    // 已有同名文件，按 fail 拒绝
+    Conflict,
+    // 超过单个文件的大小上限，附带说明
+    TooLarge(String),
+    // 未保存的原因
+    Rejected(String),
+}
+
+impl Outcome {
+    // JSON 中的 status 字段
+    fn status(&self) -> &'static str {
+        match self {
+            Outcome::Saved(_) => "saved",
+            Outcome::Overwritten(_) => "overwritten",
+            Outcome::Renamed(_) => "renamed",
+            Outcome::Skipped => "skipped",
+            Outcome::Conflict => "conflict",
+            Outcome::TooLarge(_) => "too_large",
+            Outcome::Rejected(_) => "rejected",
+        }
+    }
+
+    fn saved_as(&self) -> Option<&str> {
+        match self {
+            Outcome::Saved(name) | Outcome::Overwritten(name) | Outcome::Renamed(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    // 文件保存在子目录中时，结果里的文件名带上相对路径
+    fn in_dir(self, dir: &str) -> Outcome {
+        if dir.is_empty() {
+            return self;
+        }
+        let join = |name: String| format!("{}/{}", dir, name);
+        match self {
+            Outcome::Saved(name) => Outcome::Saved(join(name)),
+            Outcome::Overwritten(name) => Outcome::Overwritten(join(name)),
+            Outcome::Renamed(name) => Outcome::Renamed(join(name)),
+            other => other,
+        }
+    }
+
+    // 页面上显示的说明
+    fn describe(&self, requested: &str) -> String {
+        match self {
+            Outcome::Saved(name) if name == requested => "已保存".to_string(),
+            Outcome::Saved(name) => format!("已保存为 {}", name),
+            Outcome::Overwritten(_) => "已覆盖同名文件".to_string(),
+            Outcome::Renamed(name) => format!("已有同名文件，保存为 {}", name),
+            Outcome::Skipped => "已有同名文件，已跳过".to_string(),
+            Outcome::Conflict => "已有同名文件".to_string(),
+            Outcome::TooLarge(reason) | Outcome::Rejected(reason) => reason.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct FileReport {
+    // 客户端提交的文件名，上传文件夹时为相对路径
+    pub name: String,
+    pub outcome: Outcome,
+}
+
+#[derive(Serialize)]
+struct FileReportJson<'a> {
+    name: &'a str,
+    status: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    saved_as: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct UploadReportJson<'a> {
+    saved: usize,
+    total: usize,
+    files: Vec<FileReportJson<'a>>,
+}
+
+// 一次上传请求中每个文件的结果
+#[derive(Debug, Default)]
+pub struct UploadReport {
+    pub files: Vec<FileReport>,
+    // 不向客户端透露文件最终保存的名称（是否改名），用于看不到目录内容的上传者
+    hide_names: bool,
+}
+
+impl UploadReport {
+    // 保存成功的文件只报告“已保存”，不区分覆盖和改名，也不返回最终的文件名
+    pub fn hide_names(mut self) -> UploadReport {
+        self.hide_names = true;
+        self
+    }
+
+    pub fn saved(&self) -> usize {
+        self.files
+            .iter()
+            .filter(|file| file.outcome.saved_as().is_some())
+            .count()
+    }
+
+    pub fn total(&self) -> usize {
+        self.files.len()
+    }
+
+    // 所有文件都按原名保存，没有需要告诉用户的情况
+    pub fn all_saved(&self) -> bool {
+        if self.hide_names {
+            return self.saved() == self.total();
+        }
+        self.files
+            .iter()
+            .all(|file| matches!(file.outcome, Outcome::Saved(_) | Outcome::Overwritten(_)))
+    }
+
+    fn status_of(&self, outcome: &Outcome) -> &'static str {
+        match outcome.saved_as() {
+            Some(_) if self.hide_names => "saved",
+            _ => outcome.status(),
+        }
+    }
+
+    fn describe(&self, file: &FileReport) -> String {
+        match file.outcome.saved_as() {
+            Some(_) if self.hide_names => "已保存".to_string(),
+            _ => file.outcome.describe(&file.name),
+        }
+    }
+
+    // 响应状态：有文件因同名被拒绝时为 409；全部失败时，有文件超过大小上限为 413，否则为 400
+    pub fn status(&self) -> StatusCode {
+        let any = |matches: fn(&Outcome) -> bool| self.files.iter().any(|file| matches(&file.outcome));
+        if any(|outcome| matches!(outcome, Outcome::Conflict)) {
+            StatusCode::CONFLICT
+        } else if self.saved() > 0 {
+            StatusCode::OK
+        } else if any(|outcome| matches!(outcome, Outcome::TooLarge(_))) {
+            StatusCode::PAYLOAD_TOO_LARGE
+        } else if any(|outcome| matches!(outcome, Outcome::Rejected(_))) {
+            StatusCode::BAD_REQUEST
+        } else {
+            StatusCode::OK
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let files = self
+            .files
+            .iter()
+            .map(|file| FileReportJson {
+                name: &file.name,
+                status: self.status_of(&file.outcome),
+                saved_as: file.outcome.saved_as().filter(|_| !self.hide_names),
+                reason: match &file.outcome {
+                    Outcome::TooLarge(reason) | Outcome::Rejected(reason) => Some(reason),
+                    _ => None,
+                },
+            })
+            .collect();
+        serde_json::to_string(&UploadReportJson {
+            saved: self.saved(),
+            total: self.total(),
+            files,
+        })
+        .unwrap()
+    }
+
+    fn push(&mut self, name: &str, outcome: Outcome) {
+        match &outcome {
+            Outcome::TooLarge(reason) | Outcome::Rejected(reason) => tracing::info!("拒绝上传文件 {}: {}", name, reason),
+            Outcome::Skipped | Outcome::Conflict => tracing::info!("已有同名文件，未保存 {}", name),
+            _ => {}
+        }
+        self.files.push(FileReport {
+            name: name.to_string(),
+            outcome,
+        });
+    }
+}
+
+// 把客户端提交的文件名整理成 target_dir 中的一个文件名：去掉目录部分，拒绝 ..、空字符、控制字符、
+// Windows 不允许的字符和设备名，过长的文件名保留扩展名截短
+pub fn sanitize_file_name(raw: &str) -> Result<String, String> {
+    if raw.contains('\0') {
+        return Err("文件名包含空字符".to_string());
+    }
+    // 有的浏览器会带上完整的本地路径，Windows 路径用反斜杠分隔
+    if raw.split(['/', '\\']).any(|part| part.trim() == "..") {
+        return Err("文件名不能包含 ..".to_string());
+    }
+    let name = raw.rsplit(['/', '\\']).next().unwrap_or_default();
+    // Windows 会去掉结尾的点和空格，保留它们会得到两个系统上不一致的文件
+    let name = name.trim().trim_end_matches(['.', ' ']);
+    if name.is_empty() {
+        return Err("文件名为空".to_string());
+    }
+    if name.chars().any(char::is_control) {
+        return Err("文件名包含控制字符".to_string());
+    }
+    if let Some(c) = name.chars().find(|c| matches!(c, '<' | '>' | ':' | '"' | '|' | '?' | '*')) {
+        return Err(format!("文件名不能包含 {}", c));
+    }
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES.iter().any(|reserved| reserved.eq_ignore_ascii_case(stem)) {
+        return Err(format!("{} 是 Windows 保留的设备名", stem));
+    }
+    if is_temp_file(name) {
+        return Err("文件名与上传临时文件的格式相同".to_string());
+    }
+    Ok(with_suffix(name, ""))
+}
+
+// 文件名分成主干和扩展名（含点）；以点开头的文件名和过长的“扩展名”都不算扩展名
+fn split_extension(name: &str) -> (&str, &str) {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && extension.len() < 16 => (stem, &name[stem.len()..]),
+        _ => (name, ""),
+    }
+}
+
+// 在主干后面加上 suffix；超过 MAX_NAME_BYTES 时截短主干，保留扩展名
+fn with_suffix(name: &str, suffix: &str) -> String {
+    let (stem, extension) = split_extension(name);
+    let mut end = stem.len().min(MAX_NAME_BYTES - suffix.len() - extension.len());
+    while !stem.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}{}", &stem[..end], suffix, extension)
+}
+
+// 整理上传文件夹时的相对路径，返回各级目录名和文件名；每一段都按文件名的规则检查
+pub fn sanitize_relative_path(raw: &str) -> Result<(Vec<String>, String), String> {
+    if raw.contains('\0') {
+        return Err("路径包含空字符".to_string());
+    }
+    let parts: Vec<&str> = raw
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+    if parts.iter().any(|part| part.trim() == "..") {
+        return Err("路径不能包含 ..".to_string());
+    }
+    if parts.len() > MAX_PATH_DEPTH {
+        return Err(format!("目录层级超过 {} 层", MAX_PATH_DEPTH));
+    }
+    let Some((file_name, dirs)) = parts.split_last() else {
+        return Err("路径为空".to_string());
+    };
+    let dirs = dirs
+        .iter()
+        .map(|dir| sanitize_file_name(dir))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((dirs, sanitize_file_name(file_name)?))
+}
+
+// 在 target_dir 下逐级创建子目录；已有的目录直接使用，符号链接和同名文件会被拒绝，不会写到别处。
+// 同时返回新建的目录，文件最终没有保存时把它们删掉
+async fn prepare_dir(target_dir: &StdPath, dirs: &[String]) -> Result<(PathBuf, CreatedDirs), String> {
+    let mut dir = target_dir.to_path_buf();
+    let mut created = CreatedDirs::default();
+    for (depth, name) in dirs.iter().enumerate() {
+        dir.push(name);
+        let existing = match fs::symlink_metadata(&dir).await {
+            Ok(metadata) => Some(metadata),
+            Err(_) => match fs::create_dir(&dir).await {
+                Ok(()) => {
+                    created.dirs.push(dir.clone());
+                    None
+                }
+                // 同时上传的另一个请求刚创建了这个目录
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => fs::symlink_metadata(&dir).await.ok(),
+                Err(e) => {
+                    tracing::warn!("无法创建目录 {}: {}", dir.display(), e);
+                    return Err("服务器无法创建目录".to_string());
+                }
+            },
+        };
+        match existing {
+            Some(metadata) if metadata.file_type().is_symlink() => {
+                return Err(format!("{} 是符号链接", dirs[..=depth].join("/")))
+            }
+            Some(metadata) if !metadata.is_dir() => return Err(format!("{} 不是目录", dirs[..=depth].join("/"))),
+            _ => {}
+        }
+    }
+    Ok((dir, created))
+}
+
+// 一个文件新建的各级目录；守卫在 keep 之前被丢弃时从深到浅删除它们，被跳过、拒绝或中断的文件不留下空目录。
+// 同时进行的其他上传已经用到的目录不为空，会被保留
+#[derive(Debug, Default)]
+struct CreatedDirs {
+    dirs: Vec<PathBuf>,
+}
+
+impl CreatedDirs {
+    // 文件已保存，保留新建的目录
+    fn keep(mut self) {
+        self.dirs.clear();
+    }
+}
+
+impl Drop for CreatedDirs {
+    fn drop(&mut self) {
+        for dir in self.dirs.iter().rev() {
+            if std::fs::remove_dir(dir).is_err() {
+                break;
+            }
+        }
+    }
+}
+
+// path 是否位于 base 之内：去掉 base 之后只剩普通的路径段
+pub fn is_within(base: &StdPath, path: &StdPath) -> bool {
+    path.strip_prefix(base)
+        .is_ok_and(|rest| rest.components().all(|component| matches!(component, Component::Normal(_))))
+}
+
+// 把表单中的 file 字段逐块写入 target_dir，返回每个文件的结果。file 字段之前可以有一个 relative_path 字段，
+// 给出文件在上传的文件夹中的相对路径，文件会保存到 target_dir 下对应的子目录中。
+// 第一个文件之前的 on_conflict 字段覆盖 options 中的处理方式（options.conflict_field 为 true 时）。
+// permits 根据相对于 target_dir 的路径检查权限：子目录需要上传权限，覆盖同名文件需要删除权限；
+// commit 在文件完整收到后根据实际大小做最后确认，拒绝时返回原因
+pub async fn save_files<F: Future<Output = Result<(), String>>>(
+    uploads: &Arc<PartialUploads>,
+    multipart: &mut Multipart,
+    target_dir: &StdPath,
+    mut options: SaveOptions<'_>,
+    permits: impl Fn(&str, Permission) -> bool,
+    commit: impl Fn(&str, u64) -> F,
+) -> Result<UploadReport, UploadError> {
+    let mut report = UploadReport::default();
+    let mut relative_path = None;
+
+    while let Some(mut field) = multipart.next_field().await.map_err(UploadError::Multipart)? {
+        if field.name() == Some("relative_path") {
+            relative_path = Some(field.text().await.map_err(UploadError::Multipart)?);
+            continue;
+        }
+        // 已经有文件按原来的方式处理过，这时再改处理方式会悄悄地不一致，拒绝整个请求
+        if field.name() == Some("on_conflict") && options.conflict_field {
+            let value = field.text().await.map_err(UploadError::Multipart)?;
+            if !report.files.is_empty() {
+                return Err(UploadError::Field("on_conflict 字段必须位于所有文件之前".to_string()));
+            }
+            if !value.trim().is_empty() {
+                options.on_conflict = value.parse().map_err(UploadError::Field)?;
+            }
+            continue;
+        }
+        if field.name() != Some("file") {
+            continue;
+        }
+        let Some(raw_name) = field.file_name().map(str::to_string) else {
+            continue;
+        };
+        // relative_path 只对紧随其后的一个文件有效
+        let relative_path = relative_path.take().filter(|path| !path.trim().is_empty());
+        let requested = relative_path.clone().unwrap_or_else(|| raw_name.clone());
+
+        let sanitized = match &relative_path {
+            Some(path) => sanitize_relative_path(path),
+            None => sanitize_file_name(&raw_name).map(|file_name| (Vec::new(), file_name)),
+        };
+        let (dirs, file_name) = match sanitized {
+            Ok(sanitized) => sanitized,
+            Err(reason) => {
+                report.push(&requested, Outcome::Rejected(reason));
+                continue;
+            }
+        };
+        let dir_path = dirs.join("/");
+        let entry_path = if dir_path.is_empty() {
+            file_name.clone()
+        } else {
+            format!("{}/{}", dir_path, file_name)
+        };
+        if !dirs.is_empty() && !options.folders {
+            report.push(&requested, Outcome::Rejected("不支持上传文件夹".to_string()));
+            continue;
+        }
+        if !dirs.is_empty() && !permits(&dir_path, Permission::Upload) {
+            report.push(&requested, Outcome::Rejected(format!("没有上传到 {} 的权限", dir_path)));
+            continue;
+        }
+        // 途经的每一级目录和最终的文件都不能占用配置文件等的位置
+        let private = (1..=dirs.len())
+            .map(|depth| target_dir.join(dirs[..depth].join("/")))
+            .chain([target_dir.join(&entry_path)])
+            .any(|path| options.private.contains(&path));
+        if private {
+            report.push(&requested, Outcome::Rejected("文件名无效".to_string()));
+            continue;
+        }
+        // 临时文件的守卫在它之后创建、先被丢弃，删除目录时其中的半截文件已经删掉
+        let (file_dir, created_dirs) = match prepare_dir(target_dir, &dirs).await {
+            Ok(prepared) => prepared,
+            Err(reason) => {
+                report.push(&requested, Outcome::Rejected(reason));
+                continue;
+            }
+        };
+        // 整理后的每一级都只是一个路径段，这里对最终路径再检查一次
+        let file_path = file_dir.join(&file_name);
+        if file_path.parent() != Some(file_dir.as_path()) || !is_within(target_dir, &file_path) {
+            report.push(&requested, Outcome::Rejected("文件名无效".to_string()));
+            continue;
+        }
+
+        // 同名文件在写入前先检查一次，跳过或拒绝时不必接收文件内容；改名前还会再检查
+        let may_overwrite = options.on_conflict == ConflictPolicy::Overwrite && permits(&entry_path, Permission::Delete);
+        let existing = fs::symlink_metadata(&file_path).await.ok();
+        if let Some(existing) = &existing {
+            let outcome = match options.on_conflict {
+                ConflictPolicy::Skip => Some(Outcome::Skipped),
+                ConflictPolicy::Fail => Some(Outcome::Conflict),
+                ConflictPolicy::Overwrite if existing.is_dir() => Some(Outcome::Rejected("已有同名目录".to_string())),
+                ConflictPolicy::Overwrite if !may_overwrite => {
+                    Some(Outcome::Rejected("已有同名文件，没有覆盖它的权限".to_string()))
+                }
+                ConflictPolicy::Overwrite | ConflictPolicy::Rename => None,
+            };
+            if let Some(outcome) = outcome {
+                report.push(&requested, outcome);
+                continue;
+            }
+        }
+
+        // 先写入同一目录中的临时文件，完整收到后再改名；超过大小上限或中断时不会破坏同名的已有文件
+        let temp_path = file_dir.join(format!("{}{}{}", TEMP_PREFIX, tokens::random_hex(8), TEMP_SUFFIX));
+        // 写入完成前被中断（客户端断开、服务器停止）时删除半截文件；守卫在文件句柄关闭之后才被丢弃
+        let partial = uploads.track(&temp_path);
+        let mut file = match fs::File::create(&temp_path).await {
+            Ok(file) => file,
+            Err(e) => {
+                tracing::warn!("无法创建上传文件 {}: {}", temp_path.display(), e);
+                report.push(&requested, Outcome::Rejected("服务器无法创建文件".to_string()));
+                continue;
+            }
+        };
+
+        // 超过大小上限或写入失败时停止接收，字段中剩下的内容在读取下一个字段时跳过
+        let mut size = 0u64;
+        let mut failed = None;
+        while let Some(chunk) = field.chunk().await.map_err(UploadError::Multipart)? {
+            size += chunk.len() as u64;
+            if let Some(limit) = options.max_file_size.filter(|limit| size > *limit) {
+                failed = Some(Outcome::TooLarge(format!("超过单个文件 {} 的大小上限", crate::format_file_size(limit))));
+                break;
+            }
+            if let Err(e) = file.write_all(&chunk).await {
+                tracing::warn!("写入上传文件 {} 失败: {}", temp_path.display(), e);
+                failed = Some(Outcome::Rejected("服务器写入文件失败".to_string()));
+                break;
+            }
+        }
+        if failed.is_none() && file.flush().await.is_err() {
+            failed = Some(Outcome::Rejected("服务器写入文件失败".to_string()));
+        }
+        if let Some(outcome) = failed {
+            report.push(&requested, outcome);
+            continue;
+        }
+        if options.fsync {
+            if let Err(e) = file.sync_all().await {
+                tracing::warn!("无法把上传文件 {} 写入磁盘: {}", temp_path.display(), e);
+                report.push(&requested, Outcome::Rejected("服务器写入文件失败".to_string()));
+                continue;
+            }
+        }
+        drop(file);
+
+        if let Err(reason) = commit(&entry_path, size).await {
+            report.push(&requested, Outcome::Rejected(reason));
+            continue;
+        }
+        let outcome = place_file(&temp_path, &file_dir, &file_name, options.on_conflict, may_overwrite, existing.is_some())
+            .await
+            .in_dir(&dir_path);
+        if outcome.saved_as().is_some() {
+            partial.finish();
+            created_dirs.keep();
+            // 改名本身也要落盘，否则断电后可能仍是旧文件或找不到新文件
+            if options.fsync {
+                sync_dir(&file_dir).await;
+            }
+        }
+        report.push(&requested, outcome);
+    }
+    Ok(report)
+}
+
+// 把完整收到的临时文件放到最终位置；may_overwrite 为 false 时即使按 overwrite 处理也不覆盖已有文件
+async fn place_file(
+    temp_path: &StdPath,
+    dir: &StdPath,
+    file_name: &str,
+    on_conflict: ConflictPolicy,
+    may_overwrite: bool,
+    existed: bool,
+) -> Outcome {
+    if on_conflict == ConflictPolicy::Overwrite {
+        let file_path = dir.join(file_name);
+        // 没有删除权限时，接收期间出现的同名文件同样不能覆盖
+        if !may_overwrite {
+            return match place_new(temp_path, &file_path).await {
+                Ok(()) => Outcome::Saved(file_name.to_string()),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    Outcome::Rejected("已有同名文件，没有覆盖它的权限".to_string())
+                }
+                Err(e) => {
+                    tracing::warn!("无法保存上传文件 {}: {}", file_path.display(), e);
+                    Outcome::Rejected("服务器无法保存文件".to_string())
+                }
+            };
+        }
+        return match fs::rename(temp_path, &file_path).await {
+            Ok(()) if existed => Outcome::Overwritten(file_name.to_string()),
+            Ok(()) => Outcome::Saved(file_name.to_string()),
+            Err(e) => {
+                tracing::warn!("无法保存上传文件 {}: {}", file_path.display(), e);
+                Outcome::Rejected("服务器无法保存文件".to_string())
+            }
+        };
+    }
+
+    // 其他方式都不能覆盖已有文件；接收期间可能有别的请求写入了同名文件
+    for attempt in 0..MAX_RENAME_ATTEMPTS {
+        let name = match attempt {
+            0 => file_name.to_string(),
+            n => with_suffix(file_name, &format!(" ({})", n)),
+        };
+        let file_path = dir.join(&name);
+        match place_new(temp_path, &file_path).await {
+            Ok(()) if attempt == 0 => return Outcome::Saved(name),
+            Ok(()) => return Outcome::Renamed(name),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => match on_conflict {
+                ConflictPolicy::Skip => return Outcome::Skipped,
+                ConflictPolicy::Fail => return Outcome::Conflict,
+                _ => continue,
+            },
+            Err(e) => {
+                tracing::warn!("无法保存上传文件 {}: {}", file_path.display(), e);
+                return Outcome::Rejected("服务器无法保存文件".to_string());
+            }
+        }
+    }
+    Outcome::Rejected("同名文件太多，无法改名保存".to_string())
+}
+
+// 把临时文件放到 path 且不覆盖已有文件：硬链接在目标已存在时直接失败，不存在先检查再改名的竞争；
+// 文件系统不支持硬链接时退回到先检查再改名
+async fn place_new(temp_path: &StdPath, path: &StdPath) -> io::Result<()> {
+    match fs::hard_link(temp_path, path).await {
+        Ok(()) => {
+            let _ = fs::remove_file(temp_path).await;
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Err(e),
+        Err(_) if fs::symlink_metadata(path).await.is_ok() => Err(io::ErrorKind::AlreadyExists.into()),
+        Err(_) => fs::rename(temp_path, path).await,
+    }
+}
+
+// 有文件被拒绝、跳过或改名时显示的结果页；back 是返回的链接
+pub fn render_report(report: &UploadReport, back: &str) -> String {
+    let rows: String = report
+        .files
+        .iter()
+        .map(|file| {
+            let class = match file.outcome {
+                Outcome::Saved(_) | Outcome::Overwritten(_) | Outcome::Renamed(_) => "saved",
+                Outcome::Skipped => "skipped",
+                Outcome::Conflict | Outcome::TooLarge(_) | Outcome::Rejected(_) => "rejected",
+            };
+            let result = html_escape(&report.describe(file));
+            format!(
+                r#"<tr class="{}"><td>{}</td><td>{}</td></tr>"#,
+                class,
+                html_escape(&file.name),
+                result
+            )
+        })
+        .collect();
+    format!(r#"
+<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>上传结果</title>
+    <style>
+        body {{
+            font-family: -apple-system, BlinkMacSystemFont, 'Segoe UI', Roboto, sans-serif;
+            margin: 0;
+            padding: 20px;
+            background-color: #f5f5f5;
+        }}
+        .container {{
+            max-width: 720px;
+            margin: 60px auto;
+            background: white;
+            border-radius: 8px;
+            box-shadow: 0 2px 10px rgba(0,0,0,0.1);
+            overflow: hidden;
+        }}
+        .header {{
+            background: linear-gradient(135deg, #667eea 0%, #764ba2 100%);
+            color: white;
+            padding: 20px;
+            text-align: center;
+        }}
+        .header h1 {{
+            margin: 0;
+            font-size: 1.5rem;
+        }}
+        .content {{
+            padding: 20px;
+            color: #495057;
+        }}
+        table {{
+            width: 100%;
+            border-collapse: collapse;
+            margin-bottom: 16px;
+        }}
+        td {{
+            padding: 8px;
+            border-bottom: 1px solid #e9ecef;
+            word-break: break-all;
+        }}
+        .saved td:last-child {{
+            color: #1e7e34;
+        }}
+        .rejected td:last-child {{
+            color: #c82333;
+        }}
+        .skipped td:last-child {{
+            color: #856404;
+        }}
+        a {{
+            color: #007bff;
+        }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <div class="header">
+            <h1>📤 上传结果</h1>
+        </div>
+        <div class="content">
+            <p>{} 个文件中的 {} 个上传成功。</p>
+            <table>{}</table>
+            <a href="{}">返回</a>
+        </div>
+    </div>
+</body>
+</html>
+    "#, report.total(), report.saved(), rows, html_escape(back))
+}
+
+// 目录的 fsync 只在类 Unix 系统上有意义
+async fn sync_dir(dir: &StdPath) {
+    #[cfg(unix)]
+    if let Err(e) = async { fs::File::open(dir).await?.sync_all().await }.await {
+        tracing::warn!("无法把目录 {} 写入磁盘: {}", dir.display(), e);
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_keep_only_the_last_segment() {
+        assert_eq!(sanitize_file_name("report.pdf").unwrap(), "report.pdf");
+        assert_eq!(sanitize_file_name("C:\\Users\\me\\report.pdf").unwrap(), "report.pdf");
+        assert_eq!(sanitize_file_name("/home/me/report.pdf").unwrap(), "report.pdf");
+        assert_eq!(sanitize_file_name("  notes.txt. . ").unwrap(), "notes.txt");
+        assert_eq!(sanitize_file_name("it's & <b>.txt").unwrap_err(), "文件名不能包含 <");
+        assert_eq!(sanitize_file_name("it's & co.txt").unwrap(), "it's & co.txt");
+    }
+
+    #[test]
+    fn file_names_reject_parent_segments() {
+        for raw in ["..", "../x.txt", "a/../../x.txt", "..\\x.txt", " .. /x.txt"] {
+            assert!(sanitize_file_name(raw).is_err(), "{}", raw);
+        }
+        // 只是包含两个点的文件名不受影响
+        assert_eq!(sanitize_file_name("a..b.txt").unwrap(), "a..b.txt");
+        for raw in [".", "...", "/", ""] {
+            assert!(sanitize_file_name(raw).is_err(), "{}", raw);
+        }
+    }
+
+    #[test]
+    fn file_names_reject_reserved_and_special_characters() {
+        for raw in ["CON", "con.txt", "Lpt1.tar.gz", "aux .txt", "NUL"] {
+            assert!(sanitize_file_name(raw).unwrap_err().contains("保留的设备名"), "{}", raw);
+        }
+        assert_eq!(sanitize_file_name("console.txt").unwrap(), "console.txt");
+        for raw in ["say \"hi\".txt", "a:b", "a|b", "a?b", "a*b", "a\u{7}b", "a\0b"] {
+            assert!(sanitize_file_name(raw).is_err(), "{}", raw);
+        }
+        assert!(sanitize_file_name(".myhs-upload-0123abcd.part").is_err());
+    }
+
+    #[test]
+    fn long_file_names_keep_extension() {
+        let name = sanitize_file_name(&format!("{}.txt", "é".repeat(200))).unwrap();
+        assert!(name.len() <= MAX_NAME_BYTES);
+        assert!(name.ends_with("é.txt"));
+    }
+
+    #[test]
+    fn relative_paths_are_checked_segment_by_segment() {
+        let (dirs, file) = sanitize_relative_path("photos/./2024\\trip/img.jpg").unwrap();
+        assert_eq!(dirs, ["photos", "2024", "trip"]);
+        assert_eq!(file, "img.jpg");
+        for raw in ["../x.txt", "a/../../x.txt", "a/ ../x.txt", "a/CON/x.txt", "a/\"b\"/x.txt", "", "/./", "a\0/x"] {
+            assert!(sanitize_relative_path(raw).is_err(), "{}", raw);
+        }
+        let deep = vec!["d"; MAX_PATH_DEPTH + 1].join("/");
+        assert!(sanitize_relative_path(&deep).is_err());
+    }
+
+    #[test]
+    fn is_within_rejects_escapes() {
+        let base = StdPath::new("/srv/files");
+        assert!(is_within(base, StdPath::new("/srv/files/a/b.txt")));
+        assert!(is_within(base, StdPath::new("/srv/files")));
+        assert!(!is_within(base, StdPath::new("/srv/files/../secret")));
+        assert!(!is_within(base, StdPath::new("/srv/files/a/../../secret")));
+        assert!(!is_within(base, StdPath::new("/srv/files-other/a")));
+        assert!(!is_within(base, StdPath::new("/srv")));
+    }
+
+    #[test]
+    fn oversized_files_fail_the_upload_with_413() {
+        let mut report = UploadReport::default();
+        report.push("big.iso", Outcome::TooLarge("超过单个文件 1 KB 的大小上限".to_string()));
+        report.push("a<b.txt", Outcome::Rejected("文件名不能包含 <".to_string()));
+        assert_eq!(report.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        report.push("notes.txt", Outcome::Saved("notes.txt".to_string()));
+        assert_eq!(report.status(), StatusCode::OK);
+    }
+}